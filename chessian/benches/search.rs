@@ -0,0 +1,60 @@
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use chess::*;
+use chessian::chooser::*;
+use chessian::timecontrol::*;
+use chessian::*;
+
+/// The depth of the benchmarked searches.
+const DEPTH: usize = 5;
+
+/// Searches the board to `DEPTH` without a transposition table, so that every search does the
+/// same work.
+fn search(board: &HistoryBoard) -> ChooserResult {
+    best_move(
+        board,
+        TimeControl::new(None, TCMode::Depth(DEPTH)),
+        &ChooserConfig::default(),
+        std::io::sink(),
+        std::io::sink(),
+    )
+    .unwrap()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let positions = [
+        ("startpos", Board::default()),
+        (
+            "middlegame",
+            Board::from_str("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R1BQK2R w KQ - 0 8")
+                .unwrap(),
+        ),
+    ];
+    let mut group = c.benchmark_group(format!("search depth {DEPTH}"));
+    group.sample_size(10);
+    for (name, board) in positions {
+        let board = HistoryBoard::new(board);
+        // the throughput in nodes per second tells a faster evaluation apart from fewer nodes
+        let nodes = search(&board).stats.node_count;
+        println!("{name}: {nodes} nodes");
+        group.throughput(Throughput::Elements(nodes));
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let t0 = Instant::now();
+                    black_box(search(black_box(&board)));
+                    total += t0.elapsed();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
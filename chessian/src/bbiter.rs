@@ -1,6 +1,6 @@
-use chess::BitBoard;
+use chess::{BitBoard, Square};
 
-/// An iterator over the indices of the 1s in a BitBoard.
+/// An iterator over the squares of the 1s in a BitBoard.
 pub struct BitBoardIter {
     bb: BitBoard,
 }
@@ -12,14 +12,14 @@ impl BitBoardIter {
 }
 
 impl Iterator for BitBoardIter {
-    type Item = usize;
-    fn next(&mut self) -> Option<usize> {
+    type Item = Square;
+    fn next(&mut self) -> Option<Square> {
         if self.bb.0 == 0 {
             None
         } else {
-            let index = self.bb.0.trailing_zeros() as usize;
+            let index = self.bb.0.trailing_zeros() as u8;
             self.bb.0 &= self.bb.0 - 1;
-            Some(index)
+            Some(Square::new(index))
         }
     }
 }
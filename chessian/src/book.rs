@@ -0,0 +1,139 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use chess::*;
+use shakmaty::fen::Fen;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{CastlingMode, Chess, EnPassantMode};
+
+use crate::error::ChessianError;
+use crate::historyboard::HistoryBoard;
+
+/// The size in bytes of one entry of a Polyglot book file.
+const ENTRY_SIZE: usize = 16;
+
+/// An opening book in the Polyglot format.
+#[derive(Clone, Debug)]
+pub struct OpeningBook {
+    /// The entries of the book, sorted by key.
+    entries: Vec<PolyglotEntry>,
+}
+
+/// One entry of a Polyglot book: a move that may be played in the position with the given key.
+#[derive(Clone, Copy, Debug)]
+pub struct PolyglotEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl OpeningBook {
+    /// Reads a Polyglot book (`.bin`) file.
+    pub fn load(path: &str) -> Result<Self, ChessianError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Parses the contents of a Polyglot book file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChessianError> {
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(ChessianError::InvalidBook(format!(
+                "size {} is not a multiple of {ENTRY_SIZE}",
+                bytes.len()
+            )));
+        }
+        let mut entries: Vec<_> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(PolyglotEntry::parse)
+            .collect();
+        // books are supposed to be sorted already, but don't rely on it
+        entries.sort_by_key(|e| e.key);
+        Ok(Self { entries })
+    }
+
+    /// All entries for the given board, none if the board can't be hashed.
+    pub fn entries(&self, board: &Board) -> &[PolyglotEntry] {
+        let Some(key) = polyglot_hash(board) else {
+            return &[];
+        };
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Picks one of the book moves for the given board at random, according to their weights.
+    pub fn probe(&self, board: &HistoryBoard) -> Option<ChessMove> {
+        let entries = self.entries(&board.board);
+        let total: u64 = entries.iter().map(|e| e.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = RandomState::new().build_hasher().finish() % total;
+        for entry in entries {
+            if pick < entry.weight as u64 {
+                let m = entry.chess_move(&board.board);
+                return board.legal(m).then_some(m);
+            }
+            pick -= entry.weight as u64;
+        }
+        None
+    }
+}
+
+impl PolyglotEntry {
+    fn parse(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    /// Decodes the move of this entry in the context of the given board, which is needed because
+    /// Polyglot encodes castling as the king capturing its own rook.
+    pub fn chess_move(&self, board: &Board) -> ChessMove {
+        let square = |shift: u16| {
+            let file = File::from_index(((self.raw_move >> shift) & 7) as usize);
+            let rank = Rank::from_index(((self.raw_move >> (shift + 3)) & 7) as usize);
+            Square::make_square(rank, file)
+        };
+        let source = square(6);
+        let mut dest = square(0);
+        let promotion = match (self.raw_move >> 12) & 7 {
+            1 => Some(Piece::Knight),
+            2 => Some(Piece::Bishop),
+            3 => Some(Piece::Rook),
+            4 => Some(Piece::Queen),
+            _otherwise => None,
+        };
+        if board.piece_on(source) == Some(Piece::King)
+            && board.piece_on(dest) == Some(Piece::Rook)
+            && board.color_on(source) == board.color_on(dest)
+        {
+            let file = if dest.get_file() == File::H {
+                File::G
+            } else {
+                File::C
+            };
+            dest = Square::make_square(dest.get_rank(), file);
+        }
+        ChessMove::new(source, dest, promotion)
+    }
+}
+
+/// The Polyglot hash of the given board, which differs from the hash of the `chess` crate.
+/// `None` if `shakmaty` rejects the position, see `shakmaty_position`.
+pub fn polyglot_hash(board: &Board) -> Option<u64> {
+    shakmaty_position(board).map(|pos| pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0)
+}
+
+/// Converts the given board to the representation of the `shakmaty` crate. `None` for boards
+/// that the `chess` crate accepts but `shakmaty` doesn't, e.g. with impossible double checks.
+pub(crate) fn shakmaty_position(board: &Board) -> Option<Chess> {
+    board
+        .to_string()
+        .parse::<Fen>()
+        .ok()
+        .and_then(|fen| fen.into_position(CastlingMode::Standard).ok())
+}
@@ -1,45 +1,537 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
 use std::io::Write;
+use std::ops::AddAssign;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU8, Ordering},
+};
+use std::thread;
+#[cfg(not(feature = "wasm"))]
 use std::time::Instant;
 
+#[cfg(feature = "wasm")]
+use web_time::Instant;
+
 use chess::*;
 
+use crate::book::OpeningBook;
 use crate::eval::*;
 use crate::historyboard::HistoryBoard;
+use crate::is_en_passant;
+use crate::tablebase::{TB_MAX_PIECES, TablebaseProvider};
 use crate::timecontrol::*;
+use crate::uci::move_to_uci;
 
 pub const MATE_SCORE: i32 = 30_000;
 pub const INF: i32 = MATE_SCORE * 2;
+/// The number of plies for which the opening book is consulted by default.
+pub const DEFAULT_BOOK_PLIES: usize = 20;
+/// The minimum remaining depth of a node for internal iterative deepening to pay off.
+pub const IID_MIN_DEPTH: usize = 4;
+/// By how much the depth of the shallow search of internal iterative deepening is reduced.
+pub const IID_REDUCTION: usize = 2;
+/// The limit of the entries of the capture history, so that it only refines the static order of
+/// the captures.
+const CAPTURE_HISTORY_MAX: i32 = 100;
+/// The size of the transposition table of the engine and the GUI by default, in megabytes.
+pub const DEFAULT_TT_SIZE_MB: usize = 16;
+/// The default contempt in centipawns, see `ChooserConfig::contempt`.
+pub const DEFAULT_CONTEMPT: i32 = 10;
+/// How far, in centipawns, the static evaluation must be below alpha at depth 1 and 2 for the
+/// node to be razored, i.e. searched by `qsearch` only.
+const RAZORING_MARGINS: [i32; 2] = [300, 500];
+/// The default of `ChooserConfig::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// The default of `ChooserConfig::max_extensions`.
+pub const DEFAULT_MAX_EXTENSIONS: usize = 8;
+/// After this many halfmoves without a capture or pawn move, the game is drawn by the fifty-move
+/// rule.
+pub const FIFTY_MOVE_RULE_PLIES: u8 = 100;
+/// The number of entries a position may be stored in, of which the least valuable one is
+/// replaced.
+const TT_BUCKET_SIZE: usize = 4;
 
+#[derive(Clone, Debug)]
 pub struct ChooserResult {
     pub best_move: ChessMove,
     pub response: Option<ChessMove>,
     pub deep_eval: i32,
     pub reached_depth: usize,
     pub millis: u128,
+    /// Statistics of the search over all depths and threads.
+    pub stats: SearchStats,
+}
+
+/// Counters of the events during a search, to profile the search heuristics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of nodes visited by `negamax` and `qsearch`.
+    pub node_count: u64,
+    /// The number of nodes visited by `qsearch`.
+    pub qsearch_nodes: u64,
+    /// The number of positions found in the transposition table.
+    pub tt_hits: u64,
+    /// The number of nodes cut off by an entry of the transposition table.
+    pub tt_cutoffs: u64,
+    /// The number of nodes of `negamax` cut off by a move exceeding beta.
+    pub beta_cutoffs: u64,
+    /// The number of nodes of `negamax` near the leaves resolved by `qsearch` alone.
+    pub razorings: u64,
+    /// The number of searches pondering on a predicted response that was either played or not.
+    pub ponder_predictions: u64,
+    /// The number of searches pondering on a predicted response that was played.
+    pub ponder_hits: u64,
+}
+
+/// Settings of a search beyond its time control.
+#[derive(Clone, Debug)]
+pub struct ChooserConfig {
+    /// The parameters of the evaluation function used by the search.
+    pub eval: EvalConfig,
+    /// The opening book to play from instead of searching, if any.
+    pub book: Option<Arc<OpeningBook>>,
+    /// For how many plies of the game the opening book is consulted.
+    pub book_plies: usize,
+    /// The endgame tablebases to look up positions with few pieces in, if any.
+    pub tablebase: Option<Arc<dyn TablebaseProvider>>,
+    /// The number of threads searching in parallel (lazy SMP), at least 1.
+    pub threads: usize,
+    /// Limits on the strength of the search.
+    pub weakness: WeaknessConfig,
+    /// Moves that must not be chosen, e.g. to find the second best move.
+    pub exclude_moves: Vec<ChessMove>,
+    /// The transposition table shared by all searches with this configuration, if any. Its size
+    /// is given in megabytes when it is created. There is none by default, so that configs for
+    /// single searches don't allocate one.
+    pub tt: Option<Arc<TranspositionTable>>,
+    /// Milliseconds subtracted from the time limits for the latency of the communication with a
+    /// GUI.
+    pub move_overhead_ms: u128,
+    /// By how many centipawns a draw is worse than an equal position for the side to choose a
+    /// move for, so that it avoids draws it doesn't need. Negative values make it seek draws.
+    pub contempt: i32,
+    /// The ply from the root at which the search resorts to `qsearch` regardless of the remaining
+    /// depth, a safety net against overflowing the stack rather than a way to stop the search.
+    pub max_depth: usize,
+    /// The most plies a single path from the root may be extended by, e.g. for checks.
+    pub max_extensions: usize,
+    /// Called by the main search thread after each completed depth, if any.
+    pub on_depth_complete: Option<SearchDepthCallback>,
+}
+
+/// A function called with the depth, the score from the view of the side to move and the best
+/// move after each completed depth of the iterative deepening, to follow a search as it goes.
+#[derive(Clone)]
+pub struct SearchDepthCallback(pub Arc<dyn Fn(usize, i32, ChessMove) + Send + Sync>);
+
+impl fmt::Debug for SearchDepthCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SearchDepthCallback")
+    }
+}
+
+/// Limits on the strength of the engine, to make it a fair opponent for weaker players.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeaknessConfig {
+    /// The longest a search may take, regardless of its time control.
+    pub move_time_cap_ms: u128,
+    /// The probability of playing a random legal move instead of the best one.
+    pub random_best_move_prob: f32,
+    /// The deepest the iterative deepening may go, if limited.
+    pub max_depth: Option<usize>,
+}
+
+impl WeaknessConfig {
+    /// No limits at all.
+    pub const NONE: Self = Self {
+        move_time_cap_ms: u128::MAX,
+        random_best_move_prob: 0.0,
+        max_depth: None,
+    };
+    /// The highest difficulty level, which is full strength.
+    pub const MAX_LEVEL: u8 = 20;
+
+    /// A preset for the given difficulty level from 1 to `MAX_LEVEL`.
+    pub fn from_level(level: u8) -> Self {
+        let level = level.clamp(1, Self::MAX_LEVEL);
+        if level == Self::MAX_LEVEL {
+            return Self::NONE;
+        }
+        Self {
+            move_time_cap_ms: 50 * level as u128 * level as u128,
+            random_best_move_prob: (Self::MAX_LEVEL - level) as f32 * 0.025,
+            max_depth: Some(1 + level as usize / 3),
+        }
+    }
+
+    fn should_stop(&self, elapsed: u128, reached_depth: usize) -> bool {
+        elapsed >= self.move_time_cap_ms || self.max_depth.is_some_and(|d| reached_depth >= d)
+    }
+}
+
+/// How the score of a transposition table entry relates to the true score of its position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TtFlag {
+    Exact,
+    /// The true score is at least the score, as the search was cut off.
+    LowerBound,
+    /// The true score is at most the score, as no move raised alpha.
+    UpperBound,
+}
+
+/// A position searched before.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TtEntry {
+    pub hash: u64,
+    /// The depth the position was searched to.
+    pub depth: u8,
+    pub score: i32,
+    pub flag: TtFlag,
+    /// The best move, or the move that caused a cutoff, if any.
+    pub best_move: Option<ChessMove>,
+    /// The search the entry stems from, see `TranspositionTable::new_search`.
+    pub age: u8,
+}
+
+/// The positions searched before, shared by the threads of a search and kept across searches.
+pub struct TranspositionTable {
+    entries: Vec<Mutex<Option<TtEntry>>>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A table taking about the given number of megabytes.
+    pub fn new(size_mb: usize) -> Self {
+        let count =
+            (size_mb * 1024 * 1024 / size_of::<Mutex<Option<TtEntry>>>()).max(TT_BUCKET_SIZE);
+        Self {
+            entries: (0..count).map(|_| Mutex::new(None)).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    /// Begins a new search, which makes the entries of the previous ones the first to be
+    /// replaced.
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn age(&self) -> u8 {
+        self.age.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        for entry in &self.entries {
+            *entry.lock().unwrap() = None;
+        }
+    }
+
+    /// The entry of the position with the given hash, if any.
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        self.bucket(hash)
+            .iter()
+            .find_map(|slot| slot.lock().unwrap().filter(|entry| entry.hash == hash))
+    }
+
+    /// Stores the result of a search of the position with the given hash. An entry of the same
+    /// position is replaced unless it is deeper and from the current search; otherwise an empty
+    /// slot is used, or else the entry of an older search, then the one of the lowest depth.
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: usize,
+        score: i32,
+        flag: TtFlag,
+        best_move: Option<ChessMove>,
+    ) {
+        let entry = TtEntry {
+            hash,
+            depth: depth.min(u8::MAX as usize) as u8,
+            score,
+            flag,
+            best_move,
+            age: self.age(),
+        };
+        let bucket = self.bucket(hash);
+        let slots: Vec<_> = bucket.iter().map(|slot| *slot.lock().unwrap()).collect();
+        if let Some(i) = slots.iter().position(|s| s.is_some_and(|e| e.hash == hash)) {
+            let old = slots[i].unwrap();
+            if entry.depth >= old.depth || old.age != entry.age || flag == TtFlag::Exact {
+                *bucket[i].lock().unwrap() = Some(entry);
+            }
+            return;
+        }
+        let victim = (0..bucket.len())
+            .min_by_key(|i| slots[*i].map(|e| (e.age == entry.age, e.depth)))
+            .unwrap();
+        *bucket[victim].lock().unwrap() = Some(entry);
+    }
+
+    fn bucket(&self, hash: u64) -> &[Mutex<Option<TtEntry>>] {
+        let buckets = (self.entries.len() / TT_BUCKET_SIZE) as u64;
+        let start = (hash % buckets) as usize * TT_BUCKET_SIZE;
+        &self.entries[start..start + TT_BUCKET_SIZE]
+    }
+}
+
+impl fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TranspositionTable")
+            .field("entries", &self.entries.len())
+            .field("age", &self.age())
+            .finish()
+    }
+}
+
+/// State shared by all nodes of a single search.
+struct SearchContext<'a> {
+    time_control: &'a TimeControl,
+    config: &'a ChooserConfig,
+    /// Set once the result of this search is no longer needed.
+    abort: &'a AtomicBool,
+    t0: Instant,
+    stats: SearchStats,
+    tt: Option<&'a TranspositionTable>,
+    /// How often captures raised alpha in qsearch, indexed by victim and attacker.
+    capture_history: [[i32; 6]; 6],
+    /// Whether the built-in evaluation parameters are used, so that the evaluation cache of the
+    /// boards applies.
+    default_eval: bool,
+    /// The side the search chooses a move for, which the contempt applies to.
+    root_color: Color,
+    /// How many draws were scored with contempt. Their scores depend on `root_color`, so nodes
+    /// whose subtree contains one are not stored in the transposition table, which is shared
+    /// between searches for either side.
+    contempt_draws: usize,
+    /// The distance of the current node from the root.
+    ply: usize,
+    /// The number of plies the path to the current node was extended by.
+    extensions: usize,
+}
+
+/// A player choosing moves, e.g. to let different engines play each other.
+pub trait Chooser {
+    /// Chooses a move in the given position, `None` if there is no legal move.
+    fn choose(&self, board: &HistoryBoard, time_control: TimeControl) -> Option<ChooserResult>;
+}
+
+/// Chooses the best move with `best_move` and the given config.
+#[derive(Clone, Debug, Default)]
+pub struct AlphaBetaChooser(pub ChooserConfig);
+
+impl Chooser for AlphaBetaChooser {
+    fn choose(&self, board: &HistoryBoard, time_control: TimeControl) -> Option<ChooserResult> {
+        best_move(
+            board,
+            time_control,
+            &self.0,
+            std::io::sink(),
+            std::io::sink(),
+        )
+    }
+}
+
+/// Chooses a random legal move without searching, the weakest possible opponent.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomChooser;
+
+impl Chooser for RandomChooser {
+    fn choose(&self, board: &HistoryBoard, _time_control: TimeControl) -> Option<ChooserResult> {
+        let moves: Vec<_> = MoveGen::new_legal(&board.board).collect();
+        if moves.is_empty() {
+            return None;
+        }
+        let m = moves[random() as usize % moves.len()];
+        Some(ChooserResult::new(m, None, 0, 0, 0, SearchStats::default()))
+    }
+}
+
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
 }
 
 /// Most important function of the engine: Choose the best from in the given position.
 pub fn best_move(
     board: &HistoryBoard,
     time_control: TimeControl,
+    config: &ChooserConfig,
+    uci_sink: impl Write,
+    mut log: impl Write,
+) -> Option<ChooserResult> {
+    let time_control = time_control.with_move_overhead(config.move_overhead_ms);
+    let mut result = choose(board, time_control, config, uci_sink, &mut log)?;
+    if (random() as f64 / u64::MAX as f64) < config.weakness.random_best_move_prob as f64 {
+        let moves: Vec<_> = MoveGen::new_legal(&board.board)
+            .filter(|m| !config.exclude_moves.contains(m))
+            .collect();
+        result.best_move = moves[random() as usize % moves.len()];
+        result.response = None;
+        let _ = writeln!(log, "weakened to random move {}", result.best_move);
+    }
+    Some(result)
+}
+
+/// Finds up to the given number of best moves with their evaluations, best first, by searching
+/// again without the moves found so far. Each search gets the whole time control. There are
+/// fewer results if there are fewer legal moves or a search is stopped without a result.
+pub fn best_move_multipv(
+    board: &HistoryBoard,
+    lines: usize,
+    time_control: TimeControl,
+    config: &ChooserConfig,
+) -> Vec<ChooserResult> {
+    let mut config = config.clone();
+    let mut results = Vec::with_capacity(lines);
+    for _ in 0..lines {
+        let Some(result) = best_move(
+            board,
+            time_control.clone(),
+            &config,
+            std::io::sink(),
+            std::io::sink(),
+        ) else {
+            break;
+        };
+        config.exclude_moves.push(result.best_move);
+        results.push(result);
+    }
+    results
+}
+
+/// Chooses the best move from the opening book, the tablebases or by searching.
+fn choose(
+    board: &HistoryBoard,
+    time_control: TimeControl,
+    config: &ChooserConfig,
+    uci_sink: impl Write,
+    mut log: impl Write,
+) -> Option<ChooserResult> {
+    let plies = board.move_count() as usize;
+    if let Some(book) = &config.book
+        && plies < config.book_plies
+        && let Some(m) = book.probe(board)
+        && !config.exclude_moves.contains(&m)
+    {
+        let _ = writeln!(log, "book move {m}");
+        return Some(ChooserResult::new(m, None, 0, 0, 0, SearchStats::default()));
+    }
+    if let Some(prober) = &config.tablebase
+        && let Some(m) = prober.best_move(&board.board)
+        && !config.exclude_moves.contains(&m)
+    {
+        let score = prober
+            .probe_wdl(&board.board)
+            .map(|wdl| wdl.score())
+            .unwrap_or(0);
+        let _ = writeln!(log, "tablebase move {m}");
+        return Some(ChooserResult::new(
+            m,
+            None,
+            score,
+            0,
+            0,
+            SearchStats::default(),
+        ));
+    }
+
+    if let Some(tt) = &config.tt {
+        tt.new_search();
+    }
+    // Lazy SMP: helper threads search the same position independently, starting at different
+    // depths so that they don't all duplicate the work of the main thread. The deepest completed
+    // search wins.
+    let abort = AtomicBool::new(false);
+    let helper_config = ChooserConfig {
+        on_depth_complete: None,
+        ..config.clone()
+    };
+    let mut results = thread::scope(|s| {
+        let helpers: Vec<_> = (1..config.threads)
+            .map(|i| {
+                let time_control = time_control.clone();
+                let abort = &abort;
+                let helper_config = &helper_config;
+                s.spawn(move || {
+                    search(
+                        board,
+                        &time_control,
+                        helper_config,
+                        1 + i % 2,
+                        abort,
+                        std::io::sink(),
+                        std::io::sink(),
+                    )
+                })
+            })
+            .collect();
+        let main = search(board, &time_control, config, 1, &abort, uci_sink, log);
+        abort.store(true, Ordering::Relaxed);
+        let mut results = vec![main];
+        results.extend(helpers.into_iter().map(|h| h.join().ok().flatten()));
+        results
+    });
+    let mut stats = SearchStats::default();
+    for result in results.iter().flatten() {
+        stats += result.stats;
+    }
+    let mut best = results.remove(0);
+    for result in results.into_iter().flatten() {
+        if best
+            .as_ref()
+            .is_none_or(|b| result.reached_depth > b.reached_depth)
+        {
+            best = Some(result);
+        }
+    }
+    best.map(|mut b| {
+        b.stats = stats;
+        b
+    })
+}
+
+/// Iterative deepening search of a single thread, beginning at the given depth.
+fn search(
+    board: &HistoryBoard,
+    time_control: &TimeControl,
+    config: &ChooserConfig,
+    start_depth: usize,
+    abort: &AtomicBool,
     mut uci_sink: impl Write,
     mut log: impl Write,
 ) -> Option<ChooserResult> {
-    let mut candidates: Vec<_> = MoveGen::new_legal(&board.board).collect();
+    let mut candidates: Vec<_> = MoveGen::new_legal(&board.board)
+        .filter(|m| !config.exclude_moves.contains(m))
+        .collect();
     let mut best_move = None;
     let mut best_alpha = -INF;
     let mut response = None;
 
     sort_moves(&mut candidates, &board.board);
 
-    let t0 = Instant::now();
-    let mut current_depth = 1;
+    let mut ctx = SearchContext {
+        time_control,
+        config,
+        abort,
+        t0: Instant::now(),
+        stats: SearchStats::default(),
+        tt: config.tt.as_deref(),
+        capture_history: [[0; 6]; 6],
+        default_eval: config.eval == EvalConfig::DEFAULT,
+        root_color: board.side_to_move(),
+        contempt_draws: 0,
+        // the root search itself only loops over the moves of the root
+        ply: 1,
+        extensions: 0,
+    };
+    let mut current_depth = start_depth;
     'outer: loop {
-        let mut node_count = 0;
         let mut alpha = -INF;
         let mut curr_best_move = None;
         let mut curr_response = None;
         let mut curr_best_move_index = 0;
+        let mut found_mate = false;
         for (i, m) in candidates.iter().enumerate() {
             let board_after_move = board.make_move(*m);
             let (alpha_opt, response_opt) = negamax(
@@ -47,9 +539,8 @@ pub fn best_move(
                 current_depth,
                 -INF,
                 -alpha,
-                &time_control,
-                &t0,
-                &mut node_count,
+                &mut ctx,
+                true,
             );
             let Some(current_move_alpha) = alpha_opt.map(|i| -i) else {
                 let _ = write!(log, "\nout of time!");
@@ -68,30 +559,50 @@ pub fn best_move(
             }
             if alpha >= MATE_SCORE {
                 let _ = writeln!(log, "!!! MATE AT DEPTH {} !!!", current_depth);
-                best_move = curr_best_move;
-                response = response_opt;
-                best_alpha = alpha;
-                break 'outer;
+                found_mate = true;
+                break;
             }
         }
+        let time = ctx.t0.elapsed().as_millis();
+        let pv: Vec<_> = curr_best_move.into_iter().chain(curr_response).collect();
+        write_uci_info(
+            &mut uci_sink,
+            current_depth,
+            alpha,
+            ctx.stats.node_count,
+            ctx.stats.node_count as f64 * 1000.0 / time.max(1) as f64,
+            time,
+            &pv,
+        );
+        if let Some(callback) = &config.on_depth_complete
+            && let Some(m) = curr_best_move
+        {
+            (callback.0)(current_depth, alpha, m);
+        }
+        if found_mate {
+            best_move = curr_best_move;
+            response = curr_response;
+            best_alpha = alpha;
+            break;
+        }
         if alpha <= -MATE_SCORE {
             let _ = writeln!(log, "!!! WE LOSE IN MATE IN {} !!!", current_depth);
             break;
         }
-        let time = t0.elapsed().as_millis();
-        let _ = writeln!(
-            uci_sink,
-            "info depth 2 seldepth {current_depth} multipv 1 score cp {alpha} nodes {node_count} nps {:.0} time {time} pv {} {}",
-            node_count as f32 / (time as f32 / 1000.0),
-            curr_best_move.unwrap(),
-            curr_response.unwrap()
-        );
-        current_depth += 1;
         candidates.swap(0, curr_best_move_index);
         best_move = curr_best_move;
         response = curr_response;
         best_alpha = alpha;
-        if time_control.should_stop(time, current_depth - 1) {
+        // age the capture history, so that what was learned at the shallow depths doesn't
+        // dominate the ordering of the deeper ones
+        for entry in ctx.capture_history.iter_mut().flatten() {
+            *entry >>= 1;
+        }
+        current_depth += 1;
+        if time_control.should_stop(time, current_depth - 1)
+            || config.weakness.should_stop(time, current_depth - 1)
+            || abort.load(Ordering::Relaxed)
+        {
             break;
         }
     }
@@ -101,102 +612,213 @@ pub fn best_move(
             response,
             best_alpha,
             current_depth - 1,
-            t0.elapsed().as_millis(),
+            ctx.t0.elapsed().as_millis(),
+            ctx.stats,
         )
     })
 }
 
+/// Writes a UCI `info` line about a completed iteration. The score is given from the view of the
+/// side to move, as `score mate N` for a mate score.
+fn write_uci_info(
+    sink: &mut impl Write,
+    depth: usize,
+    score: i32,
+    nodes: u64,
+    nps: f64,
+    time_ms: u128,
+    pv: &[ChessMove],
+) {
+    let score = match mate_moves(score, depth) {
+        Some(moves) => format!("mate {moves}"),
+        None => format!("cp {score}"),
+    };
+    let pv = pv
+        .iter()
+        .map(|m| move_to_uci(*m))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let _ = writeln!(
+        sink,
+        "info depth {depth} multipv 1 score {score} nodes {nodes} nps {nps:.0} time {time_ms} pv {pv}"
+    );
+}
+
+/// The number of moves until mate if the given score of a search to the given depth is a mate
+/// score, negative if the side to move gets mated. As mate scores don't encode the distance, it
+/// is estimated from the depth.
+fn mate_moves(score: i32, depth: usize) -> Option<i32> {
+    (score.abs() >= MATE_SCORE).then(|| score.signum() * (depth as i32 + 3) / 2)
+}
+
 // None if ran out of time
+// The returned move is the best one, or the one that caused a beta cutoff.
+// `allow_iid` is false in the shallow search of internal iterative deepening itself.
 fn negamax(
     board: &HistoryBoard,
     depth: usize,
     mut alpha: i32,
     beta: i32,
-    time_control: &TimeControl,
-    t0: &Instant,
-    node_count: &mut usize,
+    ctx: &mut SearchContext,
+    allow_iid: bool,
 ) -> (Option<i32>, Option<ChessMove>) {
-    if depth == 0 {
-        *node_count += 1;
-        let score = qsearch(board, alpha, beta);
+    if depth == 0 || ctx.ply >= ctx.config.max_depth {
+        let score = qsearch(board, alpha, beta, ctx);
         return (Some(score), None);
     }
-    // Claim 0 depth because depth stopping only happens in the root search
-    if time_control.should_stop(t0.elapsed().as_millis(), 0) {
+    ctx.stats.node_count += 1;
+    // depth stopping only happens in the root search
+    if ctx.abort.load(Ordering::Relaxed)
+        || ctx.time_control.should_abort(ctx.t0.elapsed().as_millis())
+        || ctx
+            .config
+            .weakness
+            .should_stop(ctx.t0.elapsed().as_millis(), 0)
+    {
         return (None, None);
     }
     match board.status() {
         BoardStatus::Checkmate => (Some(-MATE_SCORE), None),
-        BoardStatus::Stalemate => {
-            let eval = if board.board.side_to_move() == Color::White {
-                eval(&board.board)
-            } else {
-                -eval(&board.board)
-            };
-            (
-                Some(if eval < -(PIECE_VALUES[2]) {
-                    MATE_SCORE / 2
-                } else {
-                    -(MATE_SCORE / 2)
-                }),
-                None,
-            )
-        }
+        BoardStatus::Stalemate => (Some(draw_score(&board.board, ctx)), None),
         BoardStatus::Ongoing => {
+            // only here, as repetitions and fifty-move draws are stalemate and the tables don't
+            // know about them
+            if let Some(prober) = &ctx.config.tablebase
+                && board.combined().popcnt() <= TB_MAX_PIECES
+                && let Some(wdl) = prober.probe_wdl(&board.board)
+            {
+                return (Some(wdl.score()), None);
+            }
+            let hash = board.get_hash();
+            let contempt_draws = ctx.contempt_draws;
+            let tt_entry = ctx.tt.and_then(|tt| tt.probe(hash));
+            if let Some(entry) = tt_entry {
+                ctx.stats.tt_hits += 1;
+                if entry.depth as usize >= depth {
+                    let score = match entry.flag {
+                        TtFlag::Exact => Some(entry.score),
+                        TtFlag::LowerBound => (entry.score >= beta).then_some(beta),
+                        TtFlag::UpperBound => (entry.score <= alpha).then_some(alpha),
+                    };
+                    if score.is_some() {
+                        ctx.stats.tt_cutoffs += 1;
+                        return (score, entry.best_move);
+                    }
+                }
+            }
+            // Razoring: far below alpha near the leaves, a quiet move is unlikely to catch up, so
+            // only the captures of the quiescence search are tried.
+            if depth <= RAZORING_MARGINS.len()
+                && *board.checkers() == EMPTY
+                && alpha.abs() < MATE_SCORE / 2
+                && static_eval(board, ctx) + RAZORING_MARGINS[depth - 1] < alpha
+            {
+                let score = qsearch(board, alpha, beta, ctx);
+                // at depth 2, the captures must confirm that the node stays below alpha
+                if depth == 1 || score < alpha {
+                    ctx.stats.razorings += 1;
+                    return (Some(score), None);
+                }
+            }
             let mut moves = MoveGen::new_legal(&board.board).collect::<Vec<_>>();
-            if depth != 1 {
-                sort_moves(&mut moves, &board.board);
+            sort_moves(&mut moves, &board.board);
+            let mut first_move = tt_entry.and_then(|entry| entry.best_move);
+            // Internal iterative deepening: without a hash move to try first, a shallow search
+            // of this node finds a likely good first move for the full depth search.
+            if first_move.is_none() && allow_iid && depth >= IID_MIN_DEPTH {
+                let (value, iid_move) =
+                    negamax(board, depth - IID_REDUCTION, alpha, beta, ctx, false);
+                if value.is_none() {
+                    return (None, None);
+                }
+                first_move = iid_move;
+            }
+            if let Some(first_move) = first_move
+                && let Some(i) = moves.iter().position(|m| *m == first_move)
+            {
+                moves[..=i].rotate_right(1);
             }
             let mut response = None;
             for m in moves {
                 let after_move = board.make_move(m);
-                let value = negamax(
-                    &after_move,
-                    depth - 1,
-                    -beta,
-                    -alpha,
-                    time_control,
-                    t0,
-                    node_count,
+                // checks are searched one ply deeper, as long as the path has extensions left
+                let extension = usize::from(
+                    *after_move.checkers() != EMPTY && ctx.extensions < ctx.config.max_extensions,
                 );
+                ctx.ply += 1;
+                ctx.extensions += extension;
+                let value = negamax(&after_move, depth - 1 + extension, -beta, -alpha, ctx, true);
+                ctx.ply -= 1;
+                ctx.extensions -= extension;
                 let Some(mut value) = value.0 else {
                     return (None, None);
                 };
                 value = -value;
                 if value >= beta {
-                    return (Some(beta), None);
+                    ctx.stats.beta_cutoffs += 1;
+                    if let Some(tt) = ctx.tt
+                        && ctx.contempt_draws == contempt_draws
+                    {
+                        tt.store(hash, depth, beta, TtFlag::LowerBound, Some(m));
+                    }
+                    return (Some(beta), Some(m));
                 }
                 if value > alpha {
                     alpha = value;
                     response = Some(m);
                 }
             }
+            if let Some(tt) = ctx.tt
+                && ctx.contempt_draws == contempt_draws
+            {
+                let flag = if response.is_some() {
+                    TtFlag::Exact
+                } else {
+                    TtFlag::UpperBound
+                };
+                tt.store(hash, depth, alpha, flag, response);
+            }
             (Some(alpha), response)
         }
     }
 }
 
-fn qsearch(board: &HistoryBoard, mut alpha: i32, beta: i32) -> i32 {
+/// The score of a drawn board, by stalemate, repetition or the fifty-move rule, for the side to
+/// move. A draw is a draw regardless of the material, so it is only biased by the contempt of the
+/// side searched for.
+fn draw_score(board: &Board, ctx: &mut SearchContext) -> i32 {
+    if ctx.config.contempt != 0 {
+        ctx.contempt_draws += 1;
+    }
+    if board.side_to_move() == ctx.root_color {
+        -ctx.config.contempt
+    } else {
+        ctx.config.contempt
+    }
+}
+
+/// The static evaluation of the board from the view of the side to move.
+fn static_eval(board: &HistoryBoard, ctx: &SearchContext) -> i32 {
+    let eval = if ctx.default_eval {
+        eval_with_cache(board)
+    } else {
+        eval_with_config(&board.board, &ctx.config.eval)
+    };
+    if board.side_to_move() == Color::White {
+        eval
+    } else {
+        -eval
+    }
+}
+
+fn qsearch(board: &HistoryBoard, mut alpha: i32, beta: i32, ctx: &mut SearchContext) -> i32 {
+    ctx.stats.node_count += 1;
+    ctx.stats.qsearch_nodes += 1;
     match board.status() {
         BoardStatus::Checkmate => -MATE_SCORE,
-        BoardStatus::Stalemate => {
-            let eval = if board.board.side_to_move() == Color::White {
-                eval(&board.board)
-            } else {
-                -eval(&board.board)
-            };
-            if eval < -(PIECE_VALUES[2]) {
-                MATE_SCORE / 2
-            } else {
-                -(MATE_SCORE / 2)
-            }
-        }
+        BoardStatus::Stalemate => draw_score(&board.board, ctx),
         BoardStatus::Ongoing => {
-            let stand_pat = if board.board.side_to_move() == Color::White {
-                eval(&board.board)
-            } else {
-                -eval(&board.board)
-            };
+            let stand_pat = static_eval(board, ctx);
             if stand_pat >= beta {
                 return beta;
             }
@@ -206,11 +828,23 @@ fn qsearch(board: &HistoryBoard, mut alpha: i32, beta: i32) -> i32 {
             let mut moves = MoveGen::new_legal(&board.board)
                 .filter(|m| !is_quiet(m, board))
                 .collect::<Vec<_>>();
-            sort_moves(&mut moves, &board.board);
+            let history = ctx.capture_history;
+            moves.sort_by_key(|m| {
+                let (victim, attacker) = capture_history_index(m, board);
+                (
+                    -(get_move_prio(m, board) + history[victim][attacker]),
+                    move_key(m),
+                )
+            });
             for m in moves {
                 let after_move = board.make_move(m);
-                let mut value = qsearch(&after_move, -beta, -alpha);
+                let mut value = qsearch(&after_move, -beta, -alpha, ctx);
                 value = -value;
+                if value > alpha {
+                    let (victim, attacker) = capture_history_index(&m, board);
+                    let entry = &mut ctx.capture_history[victim][attacker];
+                    *entry = (*entry + 1).min(CAPTURE_HISTORY_MAX);
+                }
                 if value >= beta {
                     return beta;
                 }
@@ -223,16 +857,85 @@ fn qsearch(board: &HistoryBoard, mut alpha: i32, beta: i32) -> i32 {
     }
 }
 
+/// Whether qsearch skips the given move, which it does for all but the captures that don't lose
+/// material.
 fn is_quiet(m: &ChessMove, board: &Board) -> bool {
-    get_relative_capture_value(m, board) < 0
+    get_capture(m, board).is_none() || see(m, board) < 0
+}
+
+/// The victim and attacker indices of the given capture into the capture history.
+fn capture_history_index(m: &ChessMove, board: &Board) -> (usize, usize) {
+    let victim = get_capture(m, board).unwrap_or(Piece::Pawn);
+    (victim.to_index(), get_piece(m, board).to_index())
+}
+
+/// Static exchange evaluation: the material the side to move wins with the given capture, if
+/// both sides go on recapturing on its destination with their least valuable piece as long as it
+/// pays off.
+pub fn see(m: &ChessMove, board: &Board) -> i32 {
+    let dest = m.get_dest();
+    // the gains of the side making each capture, if the capturing piece is recaptured
+    let mut gain = vec![get_capture_value(m, board)];
+    let mut occupied = *board.combined() ^ BitBoard::from_square(m.get_source());
+    let mut attacker = get_piece(m, board);
+    let mut color = board.side_to_move();
+    loop {
+        let previous = gain[gain.len() - 1];
+        let next = PIECE_VALUES[attacker.to_index()] - previous;
+        gain.push(next);
+        if (-previous).max(next) < 0 {
+            break;
+        }
+        color = !color;
+        let Some((square, piece)) = least_valuable_attacker(board, dest, color, occupied) else {
+            break;
+        };
+        occupied ^= BitBoard::from_square(square);
+        attacker = piece;
+    }
+    // the last capture didn't happen, and each side may decline to recapture
+    gain.pop();
+    while gain.len() > 1 {
+        let next = gain.pop().unwrap();
+        let previous = gain.last_mut().unwrap();
+        *previous = -(-*previous).max(next);
+    }
+    gain[0]
+}
+
+/// The least valuable piece of the given color that attacks the given square, given the occupied
+/// squares, as sliding pieces attack through pieces that have already captured.
+fn least_valuable_attacker(
+    board: &Board,
+    square: Square,
+    color: Color,
+    occupied: BitBoard,
+) -> Option<(Square, Piece)> {
+    let own = board.color_combined(color) & occupied;
+    ALL_PIECES.into_iter().find_map(|piece| {
+        let attacks = match piece {
+            Piece::Pawn => get_pawn_attacks(square, !color, !EMPTY),
+            Piece::Knight => get_knight_moves(square),
+            Piece::Bishop => get_bishop_moves(square, occupied),
+            Piece::Rook => get_rook_moves(square, occupied),
+            Piece::Queen => get_bishop_moves(square, occupied) | get_rook_moves(square, occupied),
+            Piece::King => get_king_moves(square),
+        };
+        let attackers = attacks & board.pieces(piece) & own;
+        (attackers != EMPTY).then(|| (attackers.to_square(), piece))
+    })
 }
 
 fn get_piece(m: &ChessMove, board: &Board) -> Piece {
     board.piece_on(m.get_source()).unwrap()
 }
 
+/// The piece the given move captures, if any, which is a pawn beside the destination for an en
+/// passant capture.
 fn get_capture(m: &ChessMove, board: &Board) -> Option<Piece> {
-    board.piece_on(m.get_dest())
+    board
+        .piece_on(m.get_dest())
+        .or_else(|| is_en_passant(*m, board).then_some(Piece::Pawn))
 }
 
 fn get_capture_value(m: &ChessMove, board: &Board) -> i32 {
@@ -241,18 +944,59 @@ fn get_capture_value(m: &ChessMove, board: &Board) -> i32 {
         .unwrap_or(0)
 }
 
-fn get_relative_capture_value(m: &ChessMove, board: &Board) -> i32 {
-    get_capture_value(m, board) - PIECE_VALUES[get_piece(m, board).to_index()]
-}
-
 fn get_move_prio(m: &ChessMove, before: &Board) -> i32 {
     let pos_score = SQUARE_SCORES[before.side_to_move().to_index()]
         [get_piece(m, before).to_index()][m.get_dest().to_index()];
     pos_score + get_capture_value(m, before)
 }
 
-fn sort_moves(moves: &mut [ChessMove], context: &Board) {
-    moves.sort_by_key(|m| -get_move_prio(m, context));
+/// Sorts the given moves so that the most promising ones come first, which is the order in which
+/// the search tries them.
+pub fn order_moves(moves: &mut [ChessMove], board: &Board) {
+    sort_moves(moves, board);
+}
+
+/// Sorts the moves by descending priority. Moves of equal priority are ordered by `move_key`, so
+/// that the order doesn't depend on the order the moves were generated in.
+pub(crate) fn sort_moves(moves: &mut [ChessMove], context: &Board) {
+    moves.sort_by_key(|m| (-get_move_prio(m, context), move_key(m)));
+}
+
+/// A unique number of the move made up of its squares and promotion, to order moves
+/// deterministically.
+fn move_key(m: &ChessMove) -> u16 {
+    let promotion = m.get_promotion().map_or(0, |p| p.to_index() as u16 + 1);
+    ((m.get_source().to_index() as u16) << 9) | ((m.get_dest().to_index() as u16) << 3) | promotion
+}
+
+/// Formats the given number with an SI prefix, e.g. `1.4M` for 1 400 000.
+pub fn si_prefixed(n: u64) -> String {
+    match n {
+        0..1_000 => format!("{n}"),
+        1_000..1_000_000 => format!("{:.1}k", n as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1}M", n as f64 / 1e6),
+        _ => format!("{:.1}G", n as f64 / 1e9),
+    }
+}
+
+impl Default for ChooserConfig {
+    fn default() -> Self {
+        Self {
+            eval: EvalConfig::default(),
+            book: None,
+            book_plies: DEFAULT_BOOK_PLIES,
+            tablebase: None,
+            threads: 1,
+            weakness: WeaknessConfig::NONE,
+            exclude_moves: Vec::new(),
+            tt: None,
+            move_overhead_ms: 0,
+            contempt: DEFAULT_CONTEMPT,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_extensions: DEFAULT_MAX_EXTENSIONS,
+            on_depth_complete: None,
+        }
+    }
 }
 
 impl ChooserResult {
@@ -262,6 +1006,7 @@ impl ChooserResult {
         deep_eval: i32,
         reached_depth: usize,
         millis: u128,
+        stats: SearchStats,
     ) -> Self {
         Self {
             best_move,
@@ -269,6 +1014,71 @@ impl ChooserResult {
             deep_eval,
             reached_depth,
             millis,
+            stats,
+        }
+    }
+}
+
+impl ChooserResult {
+    /// The number of moves until mate if the evaluation is a mate score, negative if the side to
+    /// move gets mated. As mate scores don't encode the distance, it is estimated from the depth
+    /// at which the mate was found.
+    pub fn mate_in(&self) -> Option<i32> {
+        mate_moves(self.deep_eval, self.reached_depth)
+    }
+}
+
+impl fmt::Display for ChooserResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move={} eval=", self.best_move)?;
+        if let Some(moves) = self.mate_in() {
+            write!(f, "M{moves}")?;
+        } else {
+            write!(f, "{:+.2}", self.deep_eval as f64 / 100.0)?;
         }
+        write!(
+            f,
+            " depth={} nodes={} time={:.1}s",
+            self.reached_depth,
+            si_prefixed(self.stats.node_count),
+            self.millis as f64 / 1000.0
+        )
+    }
+}
+
+impl SearchStats {
+    /// The share of the predicted responses that were played, `None` before any prediction.
+    pub fn ponder_hit_rate(&self) -> Option<f64> {
+        (self.ponder_predictions > 0)
+            .then(|| self.ponder_hits as f64 / self.ponder_predictions as f64)
+    }
+
+    /// Prints the statistics as a table to stdout.
+    pub fn print_summary(&self) {
+        let percentage = |n: u64| 100.0 * n as f64 / self.node_count.max(1) as f64;
+        println!("{:<20}{:>14}{:>10}", "", "count", "of nodes");
+        for (name, count) in [
+            ("nodes", self.node_count),
+            ("qsearch nodes", self.qsearch_nodes),
+            ("tt hits", self.tt_hits),
+            ("tt cutoffs", self.tt_cutoffs),
+            ("beta cutoffs", self.beta_cutoffs),
+            ("razorings", self.razorings),
+        ] {
+            println!("{name:<20}{count:>14}{:>9.1}%", percentage(count));
+        }
+    }
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.node_count += other.node_count;
+        self.qsearch_nodes += other.qsearch_nodes;
+        self.tt_hits += other.tt_hits;
+        self.tt_cutoffs += other.tt_cutoffs;
+        self.beta_cutoffs += other.beta_cutoffs;
+        self.razorings += other.razorings;
+        self.ponder_predictions += other.ponder_predictions;
+        self.ponder_hits += other.ponder_hits;
     }
 }
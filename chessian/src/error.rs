@@ -0,0 +1,44 @@
+use std::fmt;
+
+use crate::fen::FenParseError;
+
+/// Errors returned by the fallible functions of this crate.
+#[derive(Debug)]
+pub enum ChessianError {
+    /// Reading a file failed.
+    Io(std::io::Error),
+    /// An opening book file is malformed.
+    InvalidBook(String),
+    /// A move string is malformed or the move is illegal in the position.
+    InvalidMove(String),
+    /// A FEN is malformed.
+    InvalidFen(FenParseError),
+    /// A PGN is malformed, apart from its moves and FEN.
+    InvalidPgn(String),
+}
+
+impl fmt::Display for ChessianError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::InvalidBook(msg) => write!(f, "invalid opening book: {msg}"),
+            Self::InvalidMove(m) => write!(f, "invalid or illegal move: {m}"),
+            Self::InvalidFen(e) => write!(f, "{e}"),
+            Self::InvalidPgn(msg) => write!(f, "invalid PGN: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ChessianError {}
+
+impl From<std::io::Error> for ChessianError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<FenParseError> for ChessianError {
+    fn from(e: FenParseError) -> Self {
+        Self::InvalidFen(e)
+    }
+}
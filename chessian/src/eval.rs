@@ -1,6 +1,8 @@
 use chess::*;
 
 use crate::bbiter::BitBoardIter;
+use crate::geometry::{chebyshev_distance, manhattan_distance};
+use crate::historyboard::HistoryBoard;
 
 /// Value of a pawn in centipawns
 pub const PAWN_VALUE: i32 = 100;
@@ -28,10 +30,217 @@ pub const PIECE_VALUES: [i32; 6] = [
 
 /// The sanction, in centipawns, of having a double pawn.
 pub const DOUBLE_PAWN_SANCTION: i32 = 45;
+/// The sanction, in centipawns, of having a backward pawn.
+pub const BACKWARD_PAWN_PENALTY: i32 = 15;
+/// The bonus, in centipawns, for each pawn defended by another pawn. It is halved in the
+/// endgame, where the pawn structure matters less than the pawns' advance.
+pub const CONNECTED_PAWN_BONUS: i32 = 8;
+/// The bonus, in centipawns, in the endgame per pawn by which a color outnumbers the enemy pawns
+/// on the queenside or kingside away from the enemy king, as the majority can create a passed
+/// pawn the king is too far away to stop.
+pub const PAWN_MAJORITY_BONUS: i32 = 15;
+/// The bonus, in centipawns, for having pieces worth at least a rook against a lone king.
+pub const MATING_NET_BASE: i32 = 100;
+/// The bonus, in centipawns, per square a lone king is pushed from the center towards the edge.
+pub const EDGE_PROXIMITY: i32 = 10;
+/// The bonus, in centipawns, per rank and file the attacking king is closer than 14 to a lone
+/// king, as it is needed to mate.
+pub const KING_PROXIMITY: i32 = 5;
+/// The bonus, in centipawns, for each rook on the seventh rank from its color's perspective.
+pub const ROOK_SEVENTH_RANK_BONUS: i32 = 30;
+/// The additional bonus, in centipawns, for two rooks on the seventh rank.
+pub const CONNECTED_ROOKS_SEVENTH_BONUS: i32 = 15;
+/// The bonus, in centipawns, for two rooks on the same rank or file with nothing between them.
+pub const CONNECTED_ROOKS_BONUS: i32 = 15;
+/// The bonus, in centipawns, per square of distance between a passed pawn and the enemy king in
+/// the endgame, as a distant king can't stop the pawn.
+pub const KING_PAWN_DISTANCE_BONUS: i32 = 10;
+/// The bonus, in centipawns, in the endgame per pawn the own king is at most
+/// `KING_PAWN_SUPPORT_DISTANCE` away from, counting ranks and files.
+pub const KING_PAWN_SUPPORT_BONUS: i32 = 10;
+/// The distance, in ranks plus files, up to which a king supports a pawn.
+pub const KING_PAWN_SUPPORT_DISTANCE: u8 = 3;
+/// The sanction, in centipawns, per rank and file between a passed pawn and the own king in the
+/// endgame, as the pawn needs the king's support.
+pub const KING_PASSED_PAWN_DISTANCE_PENALTY: i32 = 3;
+/// The bonus, in centipawns, per attack on a square of the center. Attacks on the four inner
+/// squares and attacks by pawns count double.
+pub const CENTER_ATTACK_BONUS: i32 = 5;
+/// The penalty, in centipawns, per own pawn on the squares of the color of a lone bishop, if
+/// there are more than `BAD_BISHOP_PAWNS` of them.
+pub const BAD_BISHOP_PENALTY: i32 = 8;
+/// Above this many own pawns on the squares of its color, a lone bishop is considered bad.
+pub const BAD_BISHOP_PAWNS: usize = 4;
+/// The bonus, in centipawns, per safe square behind the own pawns that the own pieces control,
+/// outside of the endgame.
+pub const SPACE_BONUS_PER_SQUARE: i32 = 2;
+/// The bonus, in centipawns, for the side to move, which has the initiative.
+pub const TEMPO_BONUS: i32 = 20;
+/// The penalty, in centipawns, for a bishop, rook or queen in the enemy half of the board that
+/// has no square to go to that is neither occupied by an own piece nor attacked by an enemy pawn.
+pub const TRAPPED_PIECE_PENALTY: i32 = 150;
+/// The penalty, in centipawns, for a knight trapped like in `TRAPPED_PIECE_PENALTY`.
+pub const TRAPPED_KNIGHT_PENALTY: i32 = 100;
+/// The bonus, in centipawns, for each castling right left outside of the endgame, to keep the
+/// option of castling.
+pub const CASTLING_AVAILABLE_BONUS: i32 = 10;
+/// The penalty, in centipawns, for a king that can't castle anymore and is not on a square it
+/// castles to or next to it, outside of the endgame.
+pub const UNCASTLED_PENALTY: i32 = 25;
+/// The penalty, in percent of its value, for a piece that is attacked by an enemy piece of lower
+/// value and not defended. The search often finds a defense, so this is only a fraction.
+pub const HANGING_PIECE_PENALTY_PERCENT: i32 = 50;
+/// The squares b1, c1, f1 and g1, on which the white king counts as castled.
+const WHITE_CASTLED_KING: BitBoard = BitBoard(0x0000_0000_0000_0066);
+/// The squares b8, c8, f8 and g8, on which the black king counts as castled.
+const BLACK_CASTLED_KING: BitBoard = BitBoard(0x6600_0000_0000_0000);
+/// The ranks 5 to 8, the half of the board in which white pieces can be trapped.
+const WHITE_TRAP_HALF: BitBoard = BitBoard(0xFFFF_FFFF_0000_0000);
+/// The ranks 1 to 4, the half of the board in which black pieces can be trapped.
+const BLACK_TRAP_HALF: BitBoard = BitBoard(0x0000_0000_FFFF_FFFF);
+/// The ranks 2 to 4, white's half of the board without the first rank.
+const WHITE_SPACE: BitBoard = BitBoard(0x0000_0000_FFFF_FF00);
+/// The ranks 5 to 7, black's half of the board without the eighth rank.
+const BLACK_SPACE: BitBoard = BitBoard(0x00FF_FFFF_0000_0000);
+/// The squares c3 to f6.
+const EXTENDED_CENTER: BitBoard = BitBoard(0x0000_3C3C_3C3C_0000);
+/// The squares d4, e4, d5 and e5.
+const CENTER: BitBoard = BitBoard(0x0000_0018_1800_0000);
+/// Below this many pieces on the board, the position is evaluated as an endgame.
+pub const ENDGAME_PIECES: u32 = 20;
+/// Below this many pieces on the board, rooks on the seventh rank are not rewarded anymore, as
+/// there is little left for them to attack.
+pub const LATE_ENDGAME_PIECES: u32 = 10;
 
+/// Tunable parameters of the evaluation function. `EvalConfig::default()` yields the built-in
+/// constants of this module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalConfig {
+    /// Piece-square-value tables, indexed by color, piece and square.
+    pub pst: [[[i32; 64]; 6]; 2],
+    /// Additional pawn scores in the endgame, indexed by color and square.
+    pub endgame_pawns: [[i32; 64]; 2],
+    /// King scores in the endgame, replacing the king's piece-square-value table.
+    pub endgame_kings: [[i32; 64]; 2],
+    /// Piece values in centipawns in the canonical order.
+    pub piece_values: [i32; 6],
+    /// The sanction, in centipawns, of having a double pawn.
+    pub double_pawn_sanction: i32,
+    /// The sanction, in centipawns, of having a backward pawn.
+    pub backward_pawn_penalty: i32,
+    /// The bonus, in centipawns, for each pawn defended by another pawn.
+    pub connected_pawn_bonus: i32,
+    /// The bonus, in centipawns, per pawn of a majority on one wing in the endgame.
+    pub pawn_majority_bonus: i32,
+    /// The bonus, in centipawns, for having pieces worth at least a rook against a lone king.
+    pub mating_net_base: i32,
+    /// The bonus, in centipawns, per square a lone king is pushed towards the edge.
+    pub edge_proximity: i32,
+    /// The bonus, in centipawns, per rank and file the attacking king is close to a lone king.
+    pub king_proximity: i32,
+    /// The bonus, in centipawns, for each rook on the seventh rank.
+    pub rook_seventh_rank_bonus: i32,
+    /// The additional bonus, in centipawns, for two rooks on the seventh rank.
+    pub connected_rooks_seventh_bonus: i32,
+    /// The bonus, in centipawns, for two rooks defending each other.
+    pub connected_rooks_bonus: i32,
+    /// The bonus, in centipawns, per square between a passed pawn and the enemy king.
+    pub king_pawn_distance_bonus: i32,
+    /// The bonus, in centipawns, per pawn the own king is close to in the endgame.
+    pub king_pawn_support_bonus: i32,
+    /// The sanction, in centipawns, per rank and file between a passed pawn and the own king.
+    pub king_passed_pawn_distance_penalty: i32,
+    /// The bonus, in centipawns, per attack on a square of the center.
+    pub center_attack_bonus: i32,
+    /// The penalty, in centipawns, per own pawn on the squares of the color of a bad bishop.
+    pub bad_bishop_penalty: i32,
+    /// The bonus, in centipawns, per safe square behind the own pawns controlled by own pieces.
+    pub space_bonus_per_square: i32,
+    /// The bonus, in centipawns, for the side to move.
+    pub tempo_bonus: i32,
+    /// The penalty, in centipawns, for a trapped bishop, rook or queen.
+    pub trapped_piece_penalty: i32,
+    /// The penalty, in centipawns, for a trapped knight.
+    pub trapped_knight_penalty: i32,
+    /// The bonus, in centipawns, for keeping the right to castle.
+    pub castling_available_bonus: i32,
+    /// The penalty, in centipawns, for an uncastled king that lost the right to castle.
+    pub uncastled_penalty: i32,
+    /// The penalty, in percent of its value, for an undefended piece attacked by a lesser piece.
+    pub hanging_piece_penalty_percent: i32,
+}
+
+impl EvalConfig {
+    /// The built-in evaluation parameters.
+    pub const DEFAULT: Self = Self {
+        pst: SQUARE_SCORES,
+        endgame_pawns: ENDGAME_PAWN_SCORES,
+        endgame_kings: ENDGAME_KING_SCORES,
+        piece_values: PIECE_VALUES,
+        double_pawn_sanction: DOUBLE_PAWN_SANCTION,
+        backward_pawn_penalty: BACKWARD_PAWN_PENALTY,
+        connected_pawn_bonus: CONNECTED_PAWN_BONUS,
+        pawn_majority_bonus: PAWN_MAJORITY_BONUS,
+        mating_net_base: MATING_NET_BASE,
+        edge_proximity: EDGE_PROXIMITY,
+        king_proximity: KING_PROXIMITY,
+        rook_seventh_rank_bonus: ROOK_SEVENTH_RANK_BONUS,
+        connected_rooks_seventh_bonus: CONNECTED_ROOKS_SEVENTH_BONUS,
+        connected_rooks_bonus: CONNECTED_ROOKS_BONUS,
+        king_pawn_distance_bonus: KING_PAWN_DISTANCE_BONUS,
+        king_pawn_support_bonus: KING_PAWN_SUPPORT_BONUS,
+        king_passed_pawn_distance_penalty: KING_PASSED_PAWN_DISTANCE_PENALTY,
+        center_attack_bonus: CENTER_ATTACK_BONUS,
+        bad_bishop_penalty: BAD_BISHOP_PENALTY,
+        space_bonus_per_square: SPACE_BONUS_PER_SQUARE,
+        tempo_bonus: TEMPO_BONUS,
+        trapped_piece_penalty: TRAPPED_PIECE_PENALTY,
+        trapped_knight_penalty: TRAPPED_KNIGHT_PENALTY,
+        castling_available_bonus: CASTLING_AVAILABLE_BONUS,
+        uncastled_penalty: UNCASTLED_PENALTY,
+        hanging_piece_penalty_percent: HANGING_PIECE_PENALTY_PERCENT,
+    };
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Evaluates the board from white's perspective using the built-in parameters.
 pub fn eval(board: &Board) -> i32 {
-    let mut result = 0;
-    let is_endgame = board.combined().popcnt() < 20;
+    eval_with_config(board, &EvalConfig::DEFAULT)
+}
+
+/// The terms of an evaluation in centipawns from white's perspective, summing up to the
+/// evaluation itself. Terms the evaluation doesn't consider are 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalBreakdown {
+    pub material: i32,
+    pub pst: i32,
+    pub doubled_pawns: i32,
+    pub passed_pawns: i32,
+    pub isolated_pawns: i32,
+    pub mobility: i32,
+    pub king_safety: i32,
+    pub other: i32,
+}
+
+/// Evaluates the board from white's perspective using the given parameters.
+pub fn eval_with_config(board: &Board, config: &EvalConfig) -> i32 {
+    eval_explain_with_config(board, config).total()
+}
+
+/// Evaluates the board like `eval`, but keeps the terms of the evaluation apart.
+pub fn eval_explain(board: &Board) -> EvalBreakdown {
+    eval_explain_with_config(board, &EvalConfig::DEFAULT)
+}
+
+/// Evaluates the board like `eval_with_config`, but keeps the terms of the evaluation apart.
+pub fn eval_explain_with_config(board: &Board, config: &EvalConfig) -> EvalBreakdown {
+    let mut result = EvalBreakdown::default();
+    let is_endgame = board.combined().popcnt() < ENDGAME_PIECES;
 
     let white_pieces = board.color_combined(Color::White);
     let black_pieces = board.color_combined(Color::Black);
@@ -46,14 +255,16 @@ pub fn eval(board: &Board) -> i32 {
     macro_rules! piece_values {
         ($op:tt, $bb_col:expr, $bb_pieces:expr, $color_index:literal, $piece_index:literal) => {
             for i in BitBoardIter::new($bb_col & $bb_pieces) {
-                result $op SQUARE_SCORES[$color_index][$piece_index][i] + PIECE_VALUES[$piece_index];
+                result.material $op config.piece_values[$piece_index];
+                result.pst $op config.pst[$color_index][$piece_index][i.to_index()];
             }
         };
         (pawns: $op:tt, $bb_col:expr, $bb_pieces:expr, $color_index:literal) => {
             if is_endgame {
                 for i in BitBoardIter::new($bb_col & $bb_pieces) {
-                    result $op SQUARE_SCORES[$color_index][0][i] + PIECE_VALUES[0];
-                    result $op ENDGAME_PAWN_SCORES[$color_index][i];
+                    result.material $op config.piece_values[0];
+                    result.pst $op config.pst[$color_index][0][i.to_index()];
+                    result.pst $op config.endgame_pawns[$color_index][i.to_index()];
                 }
             } else {
                 piece_values![$op, $bb_col, $bb_pieces, $color_index, 0]
@@ -62,7 +273,7 @@ pub fn eval(board: &Board) -> i32 {
         (kings: $op:tt, $bb_col:expr, $bb_pieces:expr, $color_index:literal) => {
             if is_endgame {
                 for i in BitBoardIter::new($bb_col & $bb_pieces) {
-                    result $op ENDGAME_KING_SCORES[$color_index][i];
+                    result.pst $op config.endgame_kings[$color_index][i.to_index()];
                 }
             } else {
                 piece_values![$op, $bb_col, $bb_pieces, $color_index, 5]
@@ -84,18 +295,558 @@ pub fn eval(board: &Board) -> i32 {
     piece_values![-=, black_pieces, queens, 1, 4];
     piece_values![kings: -=, black_pieces, kings, 1];
 
+    add_positional_terms(board, config, &mut result);
+    result
+}
+
+/// The bonus, in centipawns, for the control of the center by the given color.
+pub fn center_control(board: &Board, color: Color) -> i32 {
+    center_attacks(board, color) * CENTER_ATTACK_BONUS
+}
+
+/// The weighted number of attacks by the pieces of the given color on the center.
+fn center_attacks(board: &Board, color: Color) -> i32 {
+    let weight = |attacks: BitBoard| {
+        ((attacks & EXTENDED_CENTER).popcnt() + (attacks & CENTER).popcnt()) as i32
+    };
+    let blockers = *board.combined();
+    let mut count = 0;
+    for square in BitBoardIter::new(*board.color_combined(color)) {
+        count += match board.piece_on(square) {
+            Some(Piece::Pawn) => 2 * weight(get_pawn_attacks(square, color, !EMPTY)),
+            Some(Piece::Knight) => weight(get_knight_moves(square)),
+            Some(Piece::Bishop) => weight(get_bishop_moves(square, blockers)),
+            Some(Piece::Rook) => weight(get_rook_moves(square, blockers)),
+            Some(Piece::Queen) => {
+                weight(get_bishop_moves(square, blockers) | get_rook_moves(square, blockers))
+            }
+            Some(Piece::King) | None => 0,
+        };
+    }
+    count
+}
+
+/// The bonus for driving a lone king to the edge with the own king close by, from white's
+/// perspective.
+pub fn mating_net_bonus(board: &Board) -> i32 {
+    mating_net(board, &EvalConfig::DEFAULT)
+}
+
+/// The bonus for the side with pieces worth at least a rook against a lone king, growing as the
+/// lone king nears the edge and the kings near each other, which guides the king chase of mates
+/// like K+Q vs K and K+R vs K that the piece-square-values alone don't.
+fn mating_net(board: &Board, config: &EvalConfig) -> i32 {
+    let piece_material = |color| {
+        [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+            .into_iter()
+            .map(|piece| {
+                (board.pieces(piece) & board.color_combined(color)).popcnt() as i32
+                    * config.piece_values[piece.to_index()]
+            })
+            .sum::<i32>()
+    };
+    for (attacker, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        let is_lone_king = board.color_combined(!attacker).popcnt() == 1;
+        if is_lone_king && piece_material(attacker) >= config.piece_values[3] {
+            let lone_king = board.king_square(!attacker);
+            let (rank, file) = (
+                lone_king.get_rank().to_index(),
+                lone_king.get_file().to_index(),
+            );
+            let edge_distance = rank.min(file).min(7 - rank).min(7 - file);
+            let king_distance = manhattan_distance(lone_king, board.king_square(attacker));
+            return sign
+                * (config.mating_net_base
+                    + config.edge_proximity * (7 - edge_distance) as i32
+                    + config.king_proximity * (14 - king_distance) as i32);
+        }
+    }
+    0
+}
+
+/// The number of squares on the given color's half of the board that are behind its pawns, not
+/// attacked by enemy pawns and attacked by its pieces.
+fn space(board: &Board, color: Color) -> i32 {
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let mut behind_pawns = EMPTY;
+    for pawn in BitBoardIter::new(own_pawns) {
+        let mut square = pawn.backward(color);
+        while let Some(behind) = square {
+            behind_pawns |= BitBoard::from_square(behind);
+            square = behind.backward(color);
+        }
+    }
+    let mut enemy_pawn_attacks = EMPTY;
+    for pawn in BitBoardIter::new(enemy_pawns) {
+        enemy_pawn_attacks |= get_pawn_attacks(pawn, !color, !EMPTY);
+    }
+    let half = match color {
+        Color::White => WHITE_SPACE,
+        Color::Black => BLACK_SPACE,
+    };
+    let safe = half & behind_pawns & !enemy_pawn_attacks & attacked_squares(board, color);
+    BitBoardIter::new(safe).count() as i32
+}
+
+/// The penalty for the trapped pieces of the given color, see `TRAPPED_PIECE_PENALTY`.
+pub fn trapped_piece_penalty(board: &Board, color: Color) -> i32 {
+    trapped_pieces(board, color, &EvalConfig::DEFAULT)
+}
+
+/// The penalty for the pieces of the given color in the enemy half of the board that have no
+/// safe square to go to. In the own half, pieces are often boxed in by their own pawns before
+/// they are developed, which the piece-square-values already account for.
+fn trapped_pieces(board: &Board, color: Color, config: &EvalConfig) -> i32 {
+    let blockers = *board.combined();
+    let own_pieces = *board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let mut enemy_pawn_attacks = EMPTY;
+    for pawn in BitBoardIter::new(enemy_pawns) {
+        enemy_pawn_attacks |= get_pawn_attacks(pawn, !color, !EMPTY);
+    }
+    let half = match color {
+        Color::White => WHITE_TRAP_HALF,
+        Color::Black => BLACK_TRAP_HALF,
+    };
+    let mut penalty = 0;
+    for square in BitBoardIter::new(own_pieces & half) {
+        let (moves, square_penalty) = match board.piece_on(square) {
+            Some(Piece::Knight) => (get_knight_moves(square), config.trapped_knight_penalty),
+            Some(Piece::Bishop) => (
+                get_bishop_moves(square, blockers),
+                config.trapped_piece_penalty,
+            ),
+            Some(Piece::Rook) => (
+                get_rook_moves(square, blockers),
+                config.trapped_piece_penalty,
+            ),
+            Some(Piece::Queen) => (
+                get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+                config.trapped_piece_penalty,
+            ),
+            Some(Piece::Pawn) | Some(Piece::King) | None => continue,
+        };
+        if moves & !own_pieces & !enemy_pawn_attacks == EMPTY {
+            penalty += square_penalty;
+        }
+    }
+    penalty
+}
+
+/// The castling term of the given color, see `CASTLING_AVAILABLE_BONUS` and
+/// `UNCASTLED_PENALTY`, which is 0 in the endgame.
+pub fn castling_score(board: &Board, color: Color) -> i32 {
+    if board.combined().popcnt() < ENDGAME_PIECES {
+        0
+    } else {
+        castling(board, color, &EvalConfig::DEFAULT)
+    }
+}
+
+/// The bonus for the castling rights of the given color or, if it has none left, the penalty for
+/// a king that is not on a castled square.
+fn castling(board: &Board, color: Color, config: &EvalConfig) -> i32 {
+    let castled_king = match color {
+        Color::White => WHITE_CASTLED_KING,
+        Color::Black => BLACK_CASTLED_KING,
+    };
+    match board.castle_rights(color) {
+        CastleRights::Both => 2 * config.castling_available_bonus,
+        CastleRights::KingSide | CastleRights::QueenSide => config.castling_available_bonus,
+        CastleRights::NoRights
+            if BitBoard::from_square(board.king_square(color)) & castled_king == EMPTY =>
+        {
+            -config.uncastled_penalty
+        }
+        CastleRights::NoRights => 0,
+    }
+}
+
+/// The penalty for the hanging pieces of the given color, see `HANGING_PIECE_PENALTY_PERCENT`.
+pub fn threat_eval(board: &Board, color: Color) -> i32 {
+    hanging_pieces(board, color, &EvalConfig::DEFAULT)
+}
+
+/// The penalty for the pieces of the given color that are attacked by an enemy piece of lower
+/// value and not defended by an own piece.
+fn hanging_pieces(board: &Board, color: Color, config: &EvalConfig) -> i32 {
+    let blockers = *board.combined();
+    let mut defended = EMPTY;
+    for square in BitBoardIter::new(*board.color_combined(color)) {
+        if let Some(piece) = board.piece_on(square) {
+            defended |= piece_attacks(piece, square, color, blockers);
+        }
+    }
+    // the squares attacked by the enemy, by the index of the attacking piece
+    let mut enemy_attacks = [EMPTY; 6];
+    for square in BitBoardIter::new(*board.color_combined(!color)) {
+        if let Some(piece) = board.piece_on(square) {
+            enemy_attacks[piece.to_index()] |= piece_attacks(piece, square, !color, blockers);
+        }
+    }
+    let mut penalty = 0;
+    for square in BitBoardIter::new(board.color_combined(color) & !defended) {
+        let Some(piece) = board.piece_on(square).filter(|p| *p != Piece::King) else {
+            continue;
+        };
+        let value = config.piece_values[piece.to_index()];
+        let attacked_by_lesser_piece = ALL_PIECES.into_iter().any(|attacker| {
+            config.piece_values[attacker.to_index()] < value
+                && enemy_attacks[attacker.to_index()] & BitBoard::from_square(square) != EMPTY
+        });
+        if attacked_by_lesser_piece {
+            penalty += value * config.hanging_piece_penalty_percent / 100;
+        }
+    }
+    penalty
+}
+
+/// The squares the given piece of the given color on the given square attacks.
+fn piece_attacks(piece: Piece, square: Square, color: Color, blockers: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Pawn => get_pawn_attacks(square, color, !EMPTY),
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, blockers),
+        Piece::Rook => get_rook_moves(square, blockers),
+        Piece::Queen => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+        Piece::King => get_king_moves(square),
+    }
+}
+
+/// The squares attacked by the pieces of the given color, except for its king.
+fn attacked_squares(board: &Board, color: Color) -> BitBoard {
+    let blockers = *board.combined();
+    let mut attacks = EMPTY;
+    for square in BitBoardIter::new(*board.color_combined(color)) {
+        attacks |= match board.piece_on(square) {
+            Some(Piece::Pawn) => get_pawn_attacks(square, color, !EMPTY),
+            Some(Piece::Knight) => get_knight_moves(square),
+            Some(Piece::Bishop) => get_bishop_moves(square, blockers),
+            Some(Piece::Rook) => get_rook_moves(square, blockers),
+            Some(Piece::Queen) => {
+                get_bishop_moves(square, blockers) | get_rook_moves(square, blockers)
+            }
+            Some(Piece::King) | None => EMPTY,
+        };
+    }
+    attacks
+}
+
+/// Whether no enemy pawns can stop or capture the pawn of the given color on the given square on
+/// its way to promotion.
+fn is_passed(pawn: Square, color: Color, enemy_pawns: BitBoard) -> bool {
+    let files = get_file(pawn.get_file()) | get_adjacent_files(pawn.get_file());
+    let ahead = ALL_RANKS
+        .into_iter()
+        .filter(|rank| match color {
+            Color::White => rank.to_index() > pawn.get_rank().to_index(),
+            Color::Black => rank.to_index() < pawn.get_rank().to_index(),
+        })
+        .fold(EMPTY, |bb, rank| bb | get_rank(rank));
+    files & ahead & enemy_pawns == EMPTY
+}
+
+/// Whether the pawn of the given color on the given square is backward: its pawns on the
+/// adjacent files are all ahead of it, so none can defend it, and an enemy pawn attacks the square
+/// in front of it, so it can't advance to them safely.
+fn is_backward(pawn: Square, color: Color, own_pawns: BitBoard, enemy_pawns: BitBoard) -> bool {
+    let neighbours = get_adjacent_files(pawn.get_file()) & own_pawns;
+    let behind_or_level = ALL_RANKS
+        .into_iter()
+        .filter(|rank| match color {
+            Color::White => rank.to_index() <= pawn.get_rank().to_index(),
+            Color::Black => rank.to_index() >= pawn.get_rank().to_index(),
+        })
+        .fold(EMPTY, |bb, rank| bb | get_rank(rank));
+    neighbours != EMPTY
+        && neighbours & behind_or_level == EMPTY
+        && pawn
+            .forward(color)
+            .is_some_and(|front| get_pawn_attacks(front, color, enemy_pawns) != EMPTY)
+}
+
+/// The given pawns of the given color that are defended by another of them.
+fn connected_pawns(pawns: BitBoard, color: Color) -> BitBoard {
+    let not_a = pawns & !get_file(File::A);
+    let not_h = pawns & !get_file(File::H);
+    let defended = match color {
+        Color::White => (not_a.0 << 7) | (not_h.0 << 9),
+        Color::Black => (not_h.0 >> 7) | (not_a.0 >> 9),
+    };
+    BitBoard(defended) & pawns
+}
+
+/// Whether any two of the given rooks of one color share a rank or file with no pieces between
+/// them.
+fn are_rooks_connected(rooks: BitBoard, occupied: BitBoard) -> bool {
+    let squares: Vec<_> = BitBoardIter::new(rooks).collect();
+    squares.iter().enumerate().any(|(i, a)| {
+        squares[i + 1..].iter().any(|b| {
+            (a.get_rank() == b.get_rank() || a.get_file() == b.get_file())
+                && between(*a, *b) & occupied == EMPTY
+        })
+    })
+}
+
+/// Whether the given square is a dark one, like a1.
+pub(crate) fn is_dark_square(square: Square) -> bool {
+    (square.to_index() + square.get_rank().to_index()) % 2 == 0
+}
+
+/// The penalty for a lone bishop of one color, which can't cover the squares its own pawns are
+/// fixed on, if more than `BAD_BISHOP_PAWNS` of the given pawns stand on its color.
+fn bad_bishop_penalty(bishops: BitBoard, pawns: BitBoard, config: &EvalConfig) -> i32 {
+    if bishops.popcnt() != 1 {
+        return 0;
+    }
+    let dark = is_dark_square(bishops.to_square());
+    let same_colored = BitBoardIter::new(pawns)
+        .filter(|pawn| is_dark_square(*pawn) == dark)
+        .count();
+    if same_colored > BAD_BISHOP_PAWNS {
+        same_colored as i32 * config.bad_bishop_penalty
+    } else {
+        0
+    }
+}
+
+/// The bonus for the given rooks of one color on the given rank, their seventh.
+fn rooks_on_seventh_bonus(rooks: BitBoard, seventh: Rank, config: &EvalConfig) -> i32 {
+    let count = BitBoardIter::new(rooks)
+        .filter(|sq| sq.get_rank() == seventh)
+        .count() as i32;
+    let connected = if count >= 2 {
+        config.connected_rooks_seventh_bonus
+    } else {
+        0
+    };
+    count * config.rook_seventh_rank_bonus + connected
+}
+
+/// Adds the terms of the evaluation beyond material and piece-square-values to the given
+/// breakdown.
+fn add_positional_terms(board: &Board, config: &EvalConfig, result: &mut EvalBreakdown) {
+    let is_endgame = board.combined().popcnt() < ENDGAME_PIECES;
+    let white_pieces = board.color_combined(Color::White);
+    let black_pieces = board.color_combined(Color::Black);
+    let pawns = board.pieces(Piece::Pawn);
+    let bishops = board.pieces(Piece::Bishop);
+    let rooks = board.pieces(Piece::Rook);
+    let kings = board.pieces(Piece::King);
+
     // sanction double pawns
     let white_pawns = white_pieces & pawns;
     let black_pawns = black_pieces & pawns;
 
     for file in ALL_FILES {
         let file_bb = get_file(file);
-        result -= ((white_pawns & file_bb).popcnt() as i32
+        result.doubled_pawns -= ((white_pawns & file_bb).popcnt() as i32
             - (black_pawns & file_bb).popcnt() as i32)
-            * DOUBLE_PAWN_SANCTION;
+            * config.double_pawn_sanction;
     }
 
-    result
+    // sanction backward pawns
+    for pawn in BitBoardIter::new(white_pawns) {
+        if is_backward(pawn, Color::White, white_pawns, black_pawns) {
+            result.other -= config.backward_pawn_penalty;
+        }
+    }
+    for pawn in BitBoardIter::new(black_pawns) {
+        if is_backward(pawn, Color::Black, black_pawns, white_pawns) {
+            result.other += config.backward_pawn_penalty;
+        }
+    }
+
+    // reward pawn chains
+    let connected_pawn_bonus = if is_endgame {
+        config.connected_pawn_bonus / 2
+    } else {
+        config.connected_pawn_bonus
+    };
+    result.other += (connected_pawns(white_pawns, Color::White).popcnt() as i32
+        - connected_pawns(black_pawns, Color::Black).popcnt() as i32)
+        * connected_pawn_bonus;
+
+    // reward pawn majorities on the queenside (files a to d) or kingside (files e to h) away
+    // from the enemy king
+    if is_endgame {
+        for wing in ALL_FILES.chunks(4) {
+            let wing_bb = wing.iter().fold(EMPTY, |bb, file| bb | get_file(*file));
+            let majority =
+                (white_pawns & wing_bb).popcnt() as i32 - (black_pawns & wing_bb).popcnt() as i32;
+            let enemy_pieces = if majority > 0 {
+                black_pieces
+            } else {
+                white_pieces
+            };
+            if majority != 0 && enemy_pieces & kings & wing_bb == EMPTY {
+                result.other += majority * config.pawn_majority_bonus;
+            }
+        }
+    }
+
+    // reward passed pawns the enemy king is far away from and the own king is close to, and
+    // kings supporting their pawns
+    if is_endgame {
+        let black_king = (black_pieces & kings).to_square();
+        let white_king = (white_pieces & kings).to_square();
+        for pawn in BitBoardIter::new(white_pawns) {
+            if is_passed(pawn, Color::White, black_pawns) {
+                result.passed_pawns +=
+                    config.king_pawn_distance_bonus * chebyshev_distance(pawn, black_king) as i32;
+                result.passed_pawns -= config.king_passed_pawn_distance_penalty
+                    * manhattan_distance(pawn, white_king) as i32;
+            }
+            if manhattan_distance(pawn, white_king) <= KING_PAWN_SUPPORT_DISTANCE {
+                result.other += config.king_pawn_support_bonus;
+            }
+        }
+        for pawn in BitBoardIter::new(black_pawns) {
+            if is_passed(pawn, Color::Black, white_pawns) {
+                result.passed_pawns -=
+                    config.king_pawn_distance_bonus * chebyshev_distance(pawn, white_king) as i32;
+                result.passed_pawns += config.king_passed_pawn_distance_penalty
+                    * manhattan_distance(pawn, black_king) as i32;
+            }
+            if manhattan_distance(pawn, black_king) <= KING_PAWN_SUPPORT_DISTANCE {
+                result.other -= config.king_pawn_support_bonus;
+            }
+        }
+    }
+
+    result.other += (center_attacks(board, Color::White) - center_attacks(board, Color::Black))
+        * config.center_attack_bonus;
+
+    if board.combined().popcnt() >= LATE_ENDGAME_PIECES {
+        result.other += rooks_on_seventh_bonus(white_pieces & rooks, Rank::Seventh, config);
+        result.other -= rooks_on_seventh_bonus(black_pieces & rooks, Rank::Second, config);
+    }
+    if are_rooks_connected(white_pieces & rooks, *board.combined()) {
+        result.other += config.connected_rooks_bonus;
+    }
+    if are_rooks_connected(black_pieces & rooks, *board.combined()) {
+        result.other -= config.connected_rooks_bonus;
+    }
+
+    if !is_endgame {
+        result.other += (space(board, Color::White) - space(board, Color::Black))
+            * config.space_bonus_per_square;
+        result.king_safety +=
+            castling(board, Color::White, config) - castling(board, Color::Black, config);
+    }
+
+    result.other -= bad_bishop_penalty(white_pieces & bishops, white_pawns, config);
+    result.other += bad_bishop_penalty(black_pieces & bishops, black_pawns, config);
+
+    result.other += mating_net(board, config);
+
+    result.other -= trapped_pieces(board, Color::White, config);
+    result.other += trapped_pieces(board, Color::Black, config);
+
+    result.other -= hanging_pieces(board, Color::White, config);
+    result.other += hanging_pieces(board, Color::Black, config);
+
+    result.other += if board.side_to_move() == Color::White {
+        config.tempo_bonus
+    } else {
+        -config.tempo_bonus
+    };
+}
+
+/// The material and piece-square-value terms of the evaluation with the built-in parameters as
+/// outside of the endgame, which `HistoryBoard` keeps up to date incrementally.
+pub fn material_pst(board: &Board) -> i32 {
+    let config = &EvalConfig::DEFAULT;
+    let mut score = 0;
+    for square in BitBoardIter::new(*board.combined()) {
+        if let Some((piece, color)) = board.piece_on(square).zip(board.color_on(square)) {
+            score += piece_score(config, piece, color, square);
+        }
+    }
+    score
+}
+
+/// The change of `material_pst` by making the given legal move on the given board.
+pub fn material_pst_delta(board: &Board, m: ChessMove) -> i32 {
+    let config = &EvalConfig::DEFAULT;
+    let (source, dest) = (m.get_source(), m.get_dest());
+    let color = board.side_to_move();
+    let Some(piece) = board.piece_on(source) else {
+        return 0;
+    };
+    let mut delta = piece_score(config, m.get_promotion().unwrap_or(piece), color, dest)
+        - piece_score(config, piece, color, source);
+    if let Some(captured) = board.piece_on(dest) {
+        delta -= piece_score(config, captured, !color, dest);
+    } else if piece == Piece::Pawn && source.get_file() != dest.get_file() {
+        // en passant
+        let captured = Square::make_square(source.get_rank(), dest.get_file());
+        delta -= piece_score(config, Piece::Pawn, !color, captured);
+    }
+    if piece == Piece::King
+        && source
+            .get_file()
+            .to_index()
+            .abs_diff(dest.get_file().to_index())
+            == 2
+    {
+        // castling also moves the rook
+        let (rook_from, rook_to) = if dest.get_file() == File::G {
+            (File::H, File::F)
+        } else {
+            (File::A, File::D)
+        };
+        let rank = source.get_rank();
+        delta += piece_score(
+            config,
+            Piece::Rook,
+            color,
+            Square::make_square(rank, rook_to),
+        ) - piece_score(
+            config,
+            Piece::Rook,
+            color,
+            Square::make_square(rank, rook_from),
+        );
+    }
+    delta
+}
+
+/// The value and piece-square-value of the given piece outside of the endgame, from white's
+/// perspective.
+fn piece_score(config: &EvalConfig, piece: Piece, color: Color, square: Square) -> i32 {
+    let score = config.piece_values[piece.to_index()]
+        + config.pst[color.to_index()][piece.to_index()][square.to_index()];
+    if color == Color::White { score } else { -score }
+}
+
+/// Evaluates the board like `eval`, but takes the material and piece-square-value terms from the
+/// incrementally updated cache of the board outside of the endgame.
+pub fn eval_with_cache(board: &HistoryBoard) -> i32 {
+    if board.combined().popcnt() < ENDGAME_PIECES {
+        return eval(&board.board);
+    }
+    let mut result = EvalBreakdown {
+        material: board.eval_cache,
+        ..EvalBreakdown::default()
+    };
+    add_positional_terms(&board.board, &EvalConfig::DEFAULT, &mut result);
+    result.total()
+}
+
+impl EvalBreakdown {
+    /// The sum of all terms.
+    pub fn total(&self) -> i32 {
+        self.material
+            + self.pst
+            + self.doubled_pawns
+            + self.passed_pawns
+            + self.isolated_pawns
+            + self.mobility
+            + self.king_safety
+            + self.other
+    }
 }
 
 /// Piece-square-value table.
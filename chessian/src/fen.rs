@@ -0,0 +1,195 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chess::{ALL_FILES, ALL_RANKS, Board, Color, Piece, Rank, Square};
+
+/// The field of a FEN that is malformed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenParseErrorKind {
+    /// The ranks are malformed, or the position is impossible, e.g. without a king.
+    InvalidPiecePlacement,
+    InvalidSideToMove,
+    InvalidCastlingRights,
+    InvalidEnPassant,
+    InvalidHalfmoveClock,
+    InvalidFullmoveNumber,
+}
+
+/// A FEN that could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FenParseError {
+    pub input: String,
+    pub kind: FenParseErrorKind,
+}
+
+/// Parses the given FEN, of which the halfmove clock and fullmove number may be omitted.
+pub fn parse_fen(fen: &str) -> Result<Board, FenParseError> {
+    let error = |kind| FenParseError {
+        input: fen.to_string(),
+        kind,
+    };
+    let checks: [(FenParseErrorKind, fn(&str) -> bool); 6] = [
+        (FenParseErrorKind::InvalidPiecePlacement, is_valid_placement),
+        (FenParseErrorKind::InvalidSideToMove, |s| {
+            matches!(s, "w" | "b")
+        }),
+        (FenParseErrorKind::InvalidCastlingRights, is_valid_castling),
+        (FenParseErrorKind::InvalidEnPassant, is_valid_en_passant),
+        (FenParseErrorKind::InvalidHalfmoveClock, |s| {
+            s.parse::<u32>().is_ok()
+        }),
+        (FenParseErrorKind::InvalidFullmoveNumber, |s| {
+            s.parse::<u32>().is_ok_and(|n| n >= 1)
+        }),
+    ];
+    let fields: Vec<_> = fen.split_whitespace().collect();
+    for (i, (kind, is_valid)) in checks.into_iter().enumerate() {
+        match fields.get(i) {
+            Some(field) if !is_valid(field) => return Err(error(kind)),
+            None if i < 4 => return Err(error(kind)),
+            _otherwise => (),
+        }
+    }
+    // the fields are well-formed, so the position itself is impossible
+    Board::from_str(fen).map_err(|_| error(FenParseErrorKind::InvalidPiecePlacement))
+}
+
+/// Whether the given ranks each hold eight squares.
+fn is_valid_placement(placement: &str) -> bool {
+    let ranks: Vec<_> = placement.split('/').collect();
+    ranks.len() == 8
+        && ranks.iter().all(|rank| {
+            rank.chars().try_fold(0, |squares, c| match c {
+                '1'..='8' => c.to_digit(10).map(|empty| squares + empty),
+                'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => {
+                    Some(squares + 1)
+                }
+                _otherwise => None,
+            }) == Some(8)
+        })
+}
+
+fn is_valid_castling(castling: &str) -> bool {
+    castling == "-"
+        || (!castling.is_empty()
+            && castling.chars().all(|c| "KQkq".contains(c))
+            && "KQkq".chars().all(|c| castling.matches(c).count() <= 1))
+}
+
+fn is_valid_en_passant(en_passant: &str) -> bool {
+    en_passant == "-"
+        || Square::from_str(en_passant)
+            .is_ok_and(|square| matches!(square.get_rank(), Rank::Third | Rank::Sixth))
+}
+
+/// The FEN of the given board, which doesn't know the halfmove clock and fullmove number, so they
+/// are 0 and 1.
+pub fn board_to_fen(board: &Board) -> String {
+    fen_with_counters(board, 0, 1)
+}
+
+/// The FEN of the given board with the given halfmove clock and fullmove number.
+pub fn fen_with_counters(board: &Board, halfmove_clock: u8, fullmove_number: u16) -> String {
+    let mut fen = String::new();
+
+    for rank in ALL_RANKS.into_iter().rev() {
+        let mut empty_count = 0;
+        for file in ALL_FILES {
+            let square = Square::make_square(rank, file);
+            if let Some((piece, color)) = board.piece_on(square).zip(board.color_on(square)) {
+                if empty_count > 0 {
+                    fen.push_str(&empty_count.to_string());
+                    empty_count = 0;
+                }
+                match piece {
+                    Piece::Pawn => fen.push_str(if color == Color::White { "P" } else { "p" }),
+                    Piece::Knight => fen.push_str(if color == Color::White { "N" } else { "n" }),
+                    Piece::Bishop => fen.push_str(if color == Color::White { "B" } else { "b" }),
+                    Piece::Rook => fen.push_str(if color == Color::White { "R" } else { "r" }),
+                    Piece::Queen => fen.push_str(if color == Color::White { "Q" } else { "q" }),
+                    Piece::King => fen.push_str(if color == Color::White { "K" } else { "k" }),
+                }
+            } else {
+                empty_count += 1;
+            }
+        }
+
+        if empty_count > 0 {
+            fen.push_str(&empty_count.to_string());
+        }
+
+        if rank.to_index() > 0 {
+            fen.push('/');
+        }
+    }
+
+    // active color
+    let active_color = if board.side_to_move() == Color::White {
+        "w"
+    } else {
+        "b"
+    };
+    fen.push_str(&format!(" {active_color} "));
+
+    // castle rights
+    let mut any_castle = false;
+    if board.castle_rights(Color::White).has_kingside() {
+        any_castle = true;
+        fen.push('K');
+    }
+    if board.castle_rights(Color::White).has_queenside() {
+        any_castle = true;
+        fen.push('Q');
+    }
+    if board.castle_rights(Color::Black).has_kingside() {
+        any_castle = true;
+        fen.push('k');
+    }
+    if board.castle_rights(Color::Black).has_queenside() {
+        any_castle = true;
+        fen.push('q');
+    }
+
+    if !any_castle {
+        fen.push('-');
+    }
+    fen.push(' ');
+
+    // en passant target square, which is behind the pawn that `Board::en_passant` gives
+    if let Some(target) = board
+        .en_passant()
+        .and_then(|pawn| pawn.backward(!board.side_to_move()))
+    {
+        fen.push_str(&format!("{target}"));
+    } else {
+        fen.push('-');
+    }
+    fen.push(' ');
+
+    // halfmove clock and fullmove number
+    fen.push_str(&format!("{halfmove_clock} {fullmove_number}"));
+
+    fen
+}
+
+impl fmt::Display for FenParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = match self {
+            Self::InvalidPiecePlacement => "piece placement",
+            Self::InvalidSideToMove => "side to move",
+            Self::InvalidCastlingRights => "castling rights",
+            Self::InvalidEnPassant => "en passant square",
+            Self::InvalidHalfmoveClock => "halfmove clock",
+            Self::InvalidFullmoveNumber => "fullmove number",
+        };
+        write!(f, "invalid {field}")
+    }
+}
+
+impl fmt::Display for FenParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in FEN `{}`", self.kind, self.input)
+    }
+}
+
+impl std::error::Error for FenParseError {}
@@ -0,0 +1,19 @@
+use chess::Square;
+
+/// The number of king moves between the given squares.
+pub fn chebyshev_distance(a: Square, b: Square) -> u8 {
+    let (ranks, files) = rank_and_file_distance(a, b);
+    ranks.max(files)
+}
+
+/// The number of ranks plus the number of files between the given squares.
+pub fn manhattan_distance(a: Square, b: Square) -> u8 {
+    let (ranks, files) = rank_and_file_distance(a, b);
+    ranks + files
+}
+
+fn rank_and_file_distance(a: Square, b: Square) -> (u8, u8) {
+    let ranks = a.get_rank().to_index().abs_diff(b.get_rank().to_index());
+    let files = a.get_file().to_index().abs_diff(b.get_file().to_index());
+    (ranks as u8, files as u8)
+}
@@ -1,45 +1,276 @@
 use chess::*;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::str::FromStr;
+
+use crate::chooser::FIFTY_MOVE_RULE_PLIES;
+use crate::error::ChessianError;
+use crate::eval::{is_dark_square, material_pst, material_pst_delta};
+use crate::fen::{FenParseError, fen_with_counters, parse_fen};
 
 #[derive(Clone, Debug)]
 pub struct HistoryBoard {
     pub board: Board,
     pub history: HashMap<u64, u8>,
+    /// The number of halfmoves since the last capture or pawn move, for the fifty-move rule.
+    pub halfmove_clock: u8,
+    /// The material and piece-square-value terms of the evaluation, see `eval::material_pst`.
+    pub eval_cache: i32,
+    /// The number of halfmoves made since the board was created.
+    move_count: u16,
+    /// The fullmove number of the position the board was created with.
+    first_fullmove_number: u16,
+    /// The board, halfmove clock and evaluation cache before each move made with `make_move_mut`,
+    /// for `takeback_mut`.
+    undo_stack: Vec<(Board, u8, i32)>,
 }
 
 impl HistoryBoard {
     pub fn new(board: Board) -> Self {
         let mut history = HashMap::new();
         history.insert(board.get_hash(), 1);
-        Self { board, history }
+        Self {
+            board,
+            history,
+            halfmove_clock: 0,
+            eval_cache: material_pst(&board),
+            move_count: 0,
+            first_fullmove_number: 1,
+            undo_stack: Vec::new(),
+        }
+    }
+
+    /// The board of the given FEN, keeping its halfmove clock and fullmove number.
+    pub fn from_fen(fen: &str) -> Result<Self, FenParseError> {
+        let board = parse_fen(fen)?;
+        // the counters are valid if present, see `parse_fen`
+        let mut counters = fen
+            .split_whitespace()
+            .skip(4)
+            .map(|field| field.parse::<u32>().unwrap_or_default());
+        let halfmove_clock = counters.next().unwrap_or(0);
+        let fullmove_number = counters.next().unwrap_or(1);
+        Ok(Self {
+            halfmove_clock: u8::try_from(halfmove_clock).unwrap_or(u8::MAX),
+            first_fullmove_number: u16::try_from(fullmove_number).unwrap_or(u16::MAX),
+            ..Self::new(board)
+        })
+    }
+
+    /// The board after playing the given moves from the initial position.
+    pub fn from_moves(moves: &[ChessMove]) -> Result<Self, ChessianError> {
+        Self::from_moves_from(Board::default(), moves)
+    }
+
+    /// The board after playing the given moves from the given position, failing at the first
+    /// illegal move.
+    pub fn from_moves_from(start: Board, moves: &[ChessMove]) -> Result<Self, ChessianError> {
+        Self::new(start).apply_moves(moves)
+    }
+
+    /// The board after playing the given moves from this position, failing at the first illegal
+    /// move.
+    pub fn apply_moves(&self, moves: &[ChessMove]) -> Result<Self, ChessianError> {
+        moves.iter().try_fold(self.clone(), |board, m| {
+            if board.board.legal(*m) {
+                Ok(board.make_move(*m))
+            } else {
+                Err(ChessianError::InvalidMove(m.to_string()))
+            }
+        })
+    }
+
+    /// The board after playing the given moves in standard algebraic notation from the initial
+    /// position, e.g. `["e4", "e5", "Nf3"]`.
+    pub fn from_sans(sans: &[&str]) -> Result<Self, ChessianError> {
+        Self::new(Board::default()).apply_moves_san(sans)
+    }
+
+    /// The board after playing the given moves in standard algebraic notation (e.g. `Nf3` or
+    /// `O-O`) from this position, failing at the first illegal move.
+    pub fn apply_moves_san(&self, sans: &[&str]) -> Result<Self, ChessianError> {
+        sans.iter().try_fold(self.clone(), |board, san| {
+            ChessMove::from_san(&board.board, san)
+                .map(|m| board.make_move(m))
+                .map_err(|_| ChessianError::InvalidMove(san.to_string()))
+        })
     }
 
     pub fn make_move(&self, m: ChessMove) -> Self {
         let new_board = self.board.make_move_new(m);
         let mut history = self.history.clone();
         *(history.entry(new_board.get_hash()).or_insert(0)) += 1;
+        let is_capture = self.board.piece_on(m.get_dest()).is_some();
+        let is_pawn_move = self.board.piece_on(m.get_source()) == Some(Piece::Pawn);
+        let halfmove_clock = if is_capture || is_pawn_move {
+            0
+        } else {
+            self.halfmove_clock.saturating_add(1)
+        };
         Self {
             board: new_board,
             history,
+            halfmove_clock,
+            eval_cache: self.eval_cache + material_pst_delta(&self.board, m),
+            move_count: self.move_count.saturating_add(1),
+            first_fullmove_number: self.first_fullmove_number,
+            undo_stack: Vec::new(),
+        }
+    }
+
+    /// Makes the given move on this board, remembering the position before it so that
+    /// `takeback_mut` can restore it. Moves made with `make_move` can't be taken back.
+    pub fn make_move_mut(&mut self, m: ChessMove) {
+        let undo_stack = std::mem::take(&mut self.undo_stack);
+        let previous = (self.board, self.halfmove_clock, self.eval_cache);
+        *self = Self {
+            undo_stack,
+            ..self.make_move(m)
+        };
+        self.undo_stack.push(previous);
+    }
+
+    /// Takes back the last move made with `make_move_mut`, removing its position from the
+    /// history. `false` if there is no such move.
+    pub fn takeback_mut(&mut self) -> bool {
+        let Some((board, halfmove_clock, eval_cache)) = self.undo_stack.pop() else {
+            return false;
+        };
+        let hash = self.board.get_hash();
+        if let Some(count) = self.history.get_mut(&hash) {
+            *count -= 1;
+            if *count == 0 {
+                self.history.remove(&hash);
+            }
+        }
+        self.board = board;
+        self.halfmove_clock = halfmove_clock;
+        self.eval_cache = eval_cache;
+        self.move_count -= 1;
+        true
+    }
+
+    /// Counts the leaf nodes of the move tree of the given depth by making the moves with
+    /// `make_move`, the way the search does.
+    pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
         }
+        MoveGen::new_legal(&self.board)
+            .map(|m| self.make_move(m).perft(depth - 1))
+            .sum()
     }
 
+    /// Passes the turn to the opponent without moving a piece, clearing the en passant square.
+    /// `None` if the side to move is in check, as passing would then be illegal.
+    /// The resulting position is not recorded in the history, so that a null move never causes a
+    /// repetition.
+    pub fn make_null_move(&self) -> Option<Self> {
+        self.board.null_move().map(|board| Self {
+            board,
+            history: self.history.clone(),
+            halfmove_clock: self.halfmove_clock,
+            eval_cache: self.eval_cache,
+            move_count: self.move_count,
+            first_fullmove_number: self.first_fullmove_number,
+            undo_stack: Vec::new(),
+        })
+    }
+
+    /// Parses a move in UCI notation (e.g. `e2e4` or `a7a8q`) that is legal on this board.
+    pub fn parse_uci_move(&self, uci: &str) -> Result<ChessMove, ChessianError> {
+        ChessMove::from_str(uci)
+            .ok()
+            .filter(|m| self.board.legal(*m))
+            .ok_or_else(|| ChessianError::InvalidMove(uci.to_string()))
+    }
+
+    /// The number of halfmoves made since the board was created.
+    pub fn move_count(&self) -> u16 {
+        self.move_count
+    }
+
+    /// The fullmove number, counting from the one of the position the board was created with,
+    /// 1 unless it was created with `from_fen`.
+    pub fn fullmove_number(&self) -> u16 {
+        // whether the first move was black's, which the fullmove number counts as half a move
+        let black_began = (self.side_to_move() == Color::Black) != (self.move_count % 2 == 1);
+        self.first_fullmove_number
+            .saturating_add((self.move_count + u16::from(black_began)) / 2)
+    }
+
+    /// The FEN of this board, including its halfmove clock and its fullmove number.
+    pub fn to_fen(&self) -> String {
+        fen_with_counters(&self.board, self.halfmove_clock, self.fullmove_number())
+    }
+
+    /// The status of the board, drawn games (see `is_draw`) counting as stalemate.
     pub fn status(&self) -> BoardStatus {
-        if self
-            .history
+        match self.board.status() {
+            BoardStatus::Ongoing if self.is_draw() => BoardStatus::Stalemate,
+            status => status,
+        }
+    }
+
+    /// Whether the game is drawn by threefold repetition, by the fifty-move rule or as neither
+    /// side has the material to make progress, see `is_insufficient_material`.
+    pub fn is_draw(&self) -> bool {
+        self.history
             .get(&self.board.get_hash())
             .copied()
             .unwrap_or_default()
             >= 3
+            || self.halfmove_clock >= FIFTY_MOVE_RULE_PLIES
+            || self.is_insufficient_material()
+    }
+
+    /// Whether there are only kings and at most one minor piece left, or one bishop on each side
+    /// on squares of the same color, or one knight on each side.
+    pub fn is_insufficient_material(&self) -> bool {
+        if (self.pieces(Piece::Pawn) | self.pieces(Piece::Rook) | self.pieces(Piece::Queen))
+            != EMPTY
         {
-            BoardStatus::Stalemate
-        } else {
-            self.board.status()
+            return false;
+        }
+        let minors = |color| {
+            (self.pieces(Piece::Bishop) | self.pieces(Piece::Knight)) & self.color_combined(color)
+        };
+        let (white, black) = (minors(Color::White), minors(Color::Black));
+        match (white.popcnt(), black.popcnt()) {
+            (0, 0) | (1, 0) | (0, 1) => true,
+            (1, 1) => {
+                let (white, black) = (white.to_square(), black.to_square());
+                match (self.piece_on(white), self.piece_on(black)) {
+                    (Some(Piece::Knight), Some(Piece::Knight)) => true,
+                    (Some(Piece::Bishop), Some(Piece::Bishop)) => {
+                        is_dark_square(white) == is_dark_square(black)
+                    }
+                    _otherwise => false,
+                }
+            }
+            _otherwise => false,
         }
     }
 }
 
+/// Two boards are equal if they have the same position and the same repetition history.
+impl PartialEq for HistoryBoard {
+    fn eq(&self, other: &Self) -> bool {
+        self.board.get_hash() == other.board.get_hash() && self.history == other.history
+    }
+}
+
+impl Eq for HistoryBoard {}
+
+/// Hashes only the position, which is consistent with `PartialEq` as equal boards have equal
+/// positions.
+impl Hash for HistoryBoard {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.board.get_hash());
+    }
+}
+
 impl Deref for HistoryBoard {
     type Target = Board;
 
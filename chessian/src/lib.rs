@@ -1,7 +1,34 @@
+use chess::{Board, ChessMove, Piece};
+
 pub mod bbiter;
+pub mod book;
 pub mod chooser;
+pub mod error;
 pub mod eval;
+pub mod fen;
+pub mod geometry;
 pub mod historyboard;
+pub mod openings;
+pub mod san;
+pub mod tablebase;
+pub mod testsuite;
 pub mod timecontrol;
+pub mod uci;
 
+pub use error::ChessianError;
+pub use fen::{FenParseError, FenParseErrorKind, board_to_fen, parse_fen};
 pub use historyboard::HistoryBoard;
+pub use openings::classify_opening;
+pub use san::move_to_san;
+pub use uci::move_to_uci;
+
+/// Whether the given move on the given board is an en passant capture, that is a pawn move to
+/// the en passant square, on which there is no piece to capture.
+pub fn is_en_passant(m: ChessMove, board: &Board) -> bool {
+    board.piece_on(m.get_source()) == Some(Piece::Pawn)
+        && board.piece_on(m.get_dest()).is_none()
+        && board
+            .en_passant()
+            .and_then(|pawn| pawn.forward(board.side_to_move()))
+            == Some(m.get_dest())
+}
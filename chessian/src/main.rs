@@ -0,0 +1,326 @@
+use std::io::BufRead;
+use std::str::FromStr;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chess::*;
+
+use chessian::chooser::*;
+use chessian::timecontrol::*;
+use chessian::*;
+
+/// A running search: its stop flag, its ponder flag while pondering and the thread printing its
+/// `bestmove`.
+type Search = (Arc<AtomicBool>, Option<Arc<AtomicBool>>, JoinHandle<()>);
+
+/// The largest transposition table a GUI may ask for, in megabytes.
+const MAX_HASH_MB: usize = 1024;
+/// The most search threads a GUI may ask for.
+const MAX_THREADS: usize = 64;
+/// The default latency between the engine and a GUI, in milliseconds.
+const DEFAULT_MOVE_OVERHEAD_MS: u128 = 10;
+/// The most latency a GUI may declare, in milliseconds.
+const MAX_MOVE_OVERHEAD_MS: u128 = 5_000;
+/// The largest contempt a GUI may ask for, in either direction, in centipawns.
+const MAX_CONTEMPT: i32 = 200;
+
+/// The values of the UCI options of the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct EngineOptions {
+    hash_mb: usize,
+    threads: usize,
+    move_overhead_ms: u128,
+    contempt: i32,
+}
+
+impl EngineOptions {
+    /// Prints the `option` lines of the `uci` command, declaring the defaults and bounds.
+    fn print() {
+        println!("option name Hash type spin default {DEFAULT_TT_SIZE_MB} min 1 max {MAX_HASH_MB}");
+        println!("option name Threads type spin default 1 min 1 max {MAX_THREADS}");
+        println!(
+            "option name MoveOverhead type spin default {DEFAULT_MOVE_OVERHEAD_MS} min 0 max {MAX_MOVE_OVERHEAD_MS}"
+        );
+        println!(
+            "option name Contempt type spin default {DEFAULT_CONTEMPT} min -{MAX_CONTEMPT} max {MAX_CONTEMPT}"
+        );
+    }
+
+    /// Makes the given configuration use these options, replacing its transposition table if
+    /// the hash size differs from the given previous options.
+    fn apply(&self, previous: &Self, config: &mut ChooserConfig) {
+        if self.hash_mb != previous.hash_mb {
+            config.tt = Some(Arc::new(TranspositionTable::new(self.hash_mb)));
+        }
+        config.threads = self.threads;
+        config.move_overhead_ms = self.move_overhead_ms;
+        config.contempt = self.contempt;
+    }
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            hash_mb: DEFAULT_TT_SIZE_MB,
+            threads: 1,
+            move_overhead_ms: DEFAULT_MOVE_OVERHEAD_MS,
+            contempt: DEFAULT_CONTEMPT,
+        }
+    }
+}
+
+/// The state of the engine that persists between the commands of a UCI session.
+struct EngineState {
+    board: HistoryBoard,
+    options: EngineOptions,
+    config: ChooserConfig,
+    search: Option<Search>,
+    /// The statistics of the session beyond single searches, i.e. of pondering.
+    stats: SearchStats,
+}
+
+impl EngineState {
+    fn new() -> Self {
+        Self {
+            board: HistoryBoard::new(Board::default()),
+            options: EngineOptions::default(),
+            config: ChooserConfig {
+                tt: Some(Arc::new(TranspositionTable::new(DEFAULT_TT_SIZE_MB))),
+                move_overhead_ms: DEFAULT_MOVE_OVERHEAD_MS,
+                ..ChooserConfig::default()
+            },
+            search: None,
+            stats: SearchStats::default(),
+        }
+    }
+
+    /// Forgets everything learned about the previous game: stops a running search, returns to
+    /// the starting position and clears the transposition table so that its entries cannot
+    /// collide with positions of the new game.
+    fn reset(&mut self) {
+        stop_search(&mut self.search);
+        self.board = HistoryBoard::new(Board::default());
+        if let Some(tt) = &self.config.tt {
+            tt.clear();
+        }
+    }
+
+    /// Records whether the predicted move that the running search ponders on was played, which
+    /// lets the search continue on a hit. Does nothing if the search isn't pondering.
+    fn end_pondering(&mut self, hit: bool) {
+        let Some((_, Some(ponder_flag), _)) = &self.search else {
+            return;
+        };
+        if !ponder_flag.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        self.stats.ponder_predictions += 1;
+        if hit {
+            self.stats.ponder_hits += 1;
+        }
+        if let Some(rate) = self.stats.ponder_hit_rate() {
+            println!(
+                "info string ponder hit rate {:.0}% of {} predictions",
+                rate * 100.0,
+                self.stats.ponder_predictions
+            );
+        }
+    }
+}
+
+/// A minimal UCI frontend to the engine.
+fn main() {
+    let mut state = EngineState::new();
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name Chessian");
+                println!("id author sanj0");
+                EngineOptions::print();
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => state.reset(),
+            Some("setoption") => set_option(&mut state, tokens),
+            Some("position") => match parse_position(tokens) {
+                Ok(b) => state.board = b,
+                Err(e) => eprintln!("{e}"),
+            },
+            Some("go") => {
+                stop_search(&mut state.search);
+                state.search = Some(go(state.board.clone(), state.config.clone(), tokens));
+            }
+            Some("ponderhit") => state.end_pondering(true),
+            Some("stop") => {
+                state.end_pondering(false);
+                stop_search(&mut state.search);
+            }
+            Some("bench") => bench(),
+            Some("quit") => break,
+            _otherwise => (),
+        }
+    }
+    stop_search(&mut state.search);
+}
+
+/// Parses `position [startpos | fen <fen>] [moves <move>...]`.
+fn parse_position<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<HistoryBoard, String> {
+    let start = match tokens.next() {
+        Some("startpos") => HistoryBoard::new(Board::default()),
+        Some("fen") => {
+            let fen = tokens
+                .by_ref()
+                .take_while(|t| *t != "moves")
+                .collect::<Vec<_>>()
+                .join(" ");
+            HistoryBoard::from_fen(&fen).map_err(|e| format!("{e}"))?
+        }
+        _otherwise => return Err(String::from("expected `startpos` or `fen`")),
+    };
+    tokens
+        .skip_while(|t| *t == "moves")
+        .try_fold(start, |board, token| {
+            board.parse_uci_move(token).map(|m| board.make_move(m))
+        })
+        .map_err(|e| format!("{e}"))
+}
+
+/// Applies a `setoption name <name> value <value>` command.
+fn set_option<'a>(state: &mut EngineState, tokens: impl Iterator<Item = &'a str>) {
+    let tokens: Vec<_> = tokens.collect();
+    let mut options = state.options;
+    let valid = match tokens.as_slice() {
+        ["name", "Hash", "value", v] => parse_spin(v, 1, MAX_HASH_MB).map(|v| options.hash_mb = v),
+        ["name", "Threads", "value", v] => {
+            parse_spin(v, 1, MAX_THREADS).map(|v| options.threads = v)
+        }
+        ["name", "MoveOverhead", "value", v] => {
+            parse_spin(v, 0, MAX_MOVE_OVERHEAD_MS).map(|v| options.move_overhead_ms = v)
+        }
+        ["name", "Contempt", "value", v] => {
+            parse_spin(v, -MAX_CONTEMPT, MAX_CONTEMPT).map(|v| options.contempt = v)
+        }
+        _otherwise => {
+            eprintln!("unknown option {}", tokens.join(" "));
+            return;
+        }
+    };
+    if valid.is_none() {
+        eprintln!("invalid option value {}", tokens.join(" "));
+        return;
+    }
+    options.apply(&state.options, &mut state.config);
+    state.options = options;
+}
+
+/// Parses the value of a spin option, `None` if it is malformed or out of its bounds.
+fn parse_spin<T: FromStr + PartialOrd>(value: &str, min: T, max: T) -> Option<T> {
+    value.parse().ok().filter(|v| *v >= min && *v <= max)
+}
+
+/// Starts searching the given board according to the `go` arguments.
+fn go<'a>(
+    board: HistoryBoard,
+    config: ChooserConfig,
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> Search {
+    let mut mode = TCMode::Infinite;
+    let (mut time, mut increment) = (None, 0);
+    let mut ponder = false;
+    let white = board.side_to_move() == Color::White;
+    while let Some(token) = tokens.next() {
+        let mut value = || tokens.next().and_then(|v| v.parse::<u128>().ok());
+        match token {
+            "movetime" => mode = TCMode::MoveTime(value().unwrap_or(1_000)),
+            "depth" => mode = TCMode::Depth(value().unwrap_or(1) as usize),
+            "wtime" if white => time = value(),
+            "btime" if !white => time = value(),
+            "winc" if white => increment = value().unwrap_or(0),
+            "binc" if !white => increment = value().unwrap_or(0),
+            "infinite" => mode = TCMode::Infinite,
+            "ponder" => ponder = true,
+            _otherwise => (),
+        }
+    }
+    if let Some(time) = time {
+        mode = TCMode::GameClock { time, increment };
+    }
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let mut time_control = TimeControl::new(Some(stop_flag.clone()), mode);
+    let ponder_flag = ponder.then(|| Arc::new(AtomicBool::new(true)));
+    if let Some(ponder_flag) = &ponder_flag {
+        time_control = time_control.with_ponder_flag(ponder_flag.clone());
+    }
+    let handle = thread::spawn(move || {
+        let result = best_move(
+            &board,
+            time_control.clone(),
+            &config,
+            std::io::stdout(),
+            std::io::sink(),
+        );
+        // the GUI expects no bestmove before it sends stop or ponderhit
+        while time_control.awaits_stop() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let Some(result) = result else {
+            println!("bestmove 0000");
+            return;
+        };
+        println!("info string {result}");
+        let stats = result.stats;
+        println!(
+            "info string debug_info nodes {} qnodes {} tthits {} betacutoffs {}",
+            stats.node_count, stats.qsearch_nodes, stats.tt_hits, stats.beta_cutoffs
+        );
+        if let Some(response) = result.response {
+            println!(
+                "bestmove {} ponder {}",
+                move_to_uci(result.best_move),
+                move_to_uci(response)
+            );
+        } else {
+            println!("bestmove {}", move_to_uci(result.best_move));
+        }
+    });
+    (stop_flag, ponder_flag, handle)
+}
+
+fn stop_search(search: &mut Option<Search>) {
+    if let Some((stop_flag, _, handle)) = search.take() {
+        stop_flag.store(true, Ordering::Relaxed);
+        let _ = handle.join();
+    }
+}
+
+/// Searches the starting position to `BENCH_DEPTH` and prints the speed of the search.
+fn bench() {
+    let result = best_move(
+        &HistoryBoard::new(Board::default()),
+        TimeControl::new(None, TCMode::Bench),
+        &ChooserConfig {
+            tt: Some(Arc::new(TranspositionTable::new(DEFAULT_TT_SIZE_MB))),
+            ..ChooserConfig::default()
+        },
+        std::io::sink(),
+        std::io::sink(),
+    )
+    .unwrap();
+    println!(
+        "bench depth {} nodes {} time {} nps {:.0}",
+        result.reached_depth,
+        result.stats.node_count,
+        result.millis,
+        result.stats.node_count as f64 / (result.millis.max(1) as f64 / 1000.0)
+    );
+    result.stats.print_summary();
+}
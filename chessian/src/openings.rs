@@ -0,0 +1,482 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use chess::Board;
+
+use crate::historyboard::HistoryBoard;
+
+/// Common openings as their ECO code, their name and the moves leading to them from the initial
+/// position in standard algebraic notation.
+pub const OPENINGS: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A00", "Grob Opening", "g4"),
+    ("A00", "Van Geet Opening", "Nc3"),
+    ("A00", "Hungarian Opening", "g3"),
+    ("A00", "Mieses Opening", "d3"),
+    ("A00", "Van't Kruijs Opening", "e3"),
+    ("A00", "Saragossa Opening", "c3"),
+    ("A00", "Anderssen's Opening", "a3"),
+    ("A00", "Ware Opening", "a4"),
+    ("A00", "Amar Opening", "Nh3"),
+    ("A00", "Clemenz Opening", "h3"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A02", "Bird's Opening: From's Gambit", "f4 e5"),
+    ("A04", "Zukertort Opening", "Nf3"),
+    ("A05", "Zukertort Opening: Quiet System", "Nf3 Nf6"),
+    ("A06", "Zukertort Opening", "Nf3 d5"),
+    ("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    ("A09", "Reti Opening", "Nf3 d5 c4"),
+    ("A10", "English Opening", "c4"),
+    ("A13", "English Opening: Agincourt Defense", "c4 e6"),
+    ("A15", "English Opening: Anglo-Indian Defense", "c4 Nf6"),
+    ("A20", "English Opening: King's English Variation", "c4 e5"),
+    ("A30", "English Opening: Symmetrical Variation", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A40", "Englund Gambit", "d4 e5"),
+    ("A43", "Old Benoni Defense", "d4 c5"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+    ("A46", "Indian Defense: Knights Variation", "d4 Nf6 Nf3"),
+    ("A47", "Queen's Indian Defense", "d4 Nf6 Nf3 b6"),
+    ("A50", "Indian Defense: Normal Variation", "d4 Nf6 c4"),
+    ("A51", "Budapest Gambit", "d4 Nf6 c4 e5"),
+    ("A53", "Old Indian Defense", "d4 Nf6 c4 d6"),
+    ("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A60", "Modern Benoni", "d4 Nf6 c4 c5 d5 e6"),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("A82", "Dutch Defense: Staunton Gambit", "d4 f5 e4"),
+    ("B00", "King's Pawn Game", "e4"),
+    ("B00", "Nimzowitsch Defense", "e4 Nc6"),
+    ("B00", "Owen Defense", "e4 b6"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    (
+        "B01",
+        "Scandinavian Defense: Modern Variation",
+        "e4 d5 exd5 Nf6",
+    ),
+    (
+        "B01",
+        "Scandinavian Defense: Main Line",
+        "e4 d5 exd5 Qxd5 Nc3 Qa5",
+    ),
+    ("B02", "Alekhine Defense", "e4 Nf6"),
+    (
+        "B03",
+        "Alekhine Defense: Four Pawns Attack",
+        "e4 Nf6 e5 Nd5 d4 d6 c4 Nb6 f4",
+    ),
+    (
+        "B04",
+        "Alekhine Defense: Modern Variation",
+        "e4 Nf6 e5 Nd5 d4 d6 Nf3",
+    ),
+    ("B06", "Modern Defense", "e4 g6"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+    (
+        "B09",
+        "Pirc Defense: Austrian Attack",
+        "e4 d6 d4 Nf6 Nc3 g6 f4",
+    ),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    (
+        "B11",
+        "Caro-Kann Defense: Two Knights Attack",
+        "e4 c6 Nc3 d5 Nf3",
+    ),
+    (
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e4 c6 d4 d5 e5",
+    ),
+    (
+        "B13",
+        "Caro-Kann Defense: Exchange Variation",
+        "e4 c6 d4 d5 exd5 cxd5",
+    ),
+    (
+        "B13",
+        "Caro-Kann Defense: Panov Attack",
+        "e4 c6 d4 d5 exd5 cxd5 c4",
+    ),
+    ("B15", "Caro-Kann Defense: Main Line", "e4 c6 d4 d5 Nc3"),
+    (
+        "B17",
+        "Caro-Kann Defense: Karpov Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7",
+    ),
+    (
+        "B18",
+        "Caro-Kann Defense: Classical Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5",
+    ),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    ("B20", "Sicilian Defense: Wing Gambit", "e4 c5 b4"),
+    (
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e4 c5 d4 cxd4 c3",
+    ),
+    ("B22", "Sicilian Defense: Alapin Variation", "e4 c5 c3"),
+    ("B23", "Sicilian Defense: Closed", "e4 c5 Nc3"),
+    (
+        "B23",
+        "Sicilian Defense: Grand Prix Attack",
+        "e4 c5 Nc3 Nc6 f4",
+    ),
+    (
+        "B27",
+        "Sicilian Defense: Hyperaccelerated Dragon",
+        "e4 c5 Nf3 g6",
+    ),
+    ("B30", "Sicilian Defense: Old Sicilian", "e4 c5 Nf3 Nc6"),
+    (
+        "B31",
+        "Sicilian Defense: Rossolimo Variation",
+        "e4 c5 Nf3 Nc6 Bb5",
+    ),
+    (
+        "B32",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4",
+    ),
+    (
+        "B32",
+        "Sicilian Defense: Kalashnikov Variation",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 e5 Nb5 d6",
+    ),
+    (
+        "B33",
+        "Sicilian Defense: Sveshnikov Variation",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5",
+    ),
+    (
+        "B35",
+        "Sicilian Defense: Accelerated Dragon",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6",
+    ),
+    ("B40", "Sicilian Defense: French Variation", "e4 c5 Nf3 e6"),
+    (
+        "B42",
+        "Sicilian Defense: Kan Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6",
+    ),
+    (
+        "B44",
+        "Sicilian Defense: Taimanov Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6",
+    ),
+    (
+        "B45",
+        "Sicilian Defense: Four Knights Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6",
+    ),
+    ("B50", "Sicilian Defense: Modern Variations", "e4 c5 Nf3 d6"),
+    (
+        "B51",
+        "Sicilian Defense: Moscow Variation",
+        "e4 c5 Nf3 d6 Bb5",
+    ),
+    ("B54", "Sicilian Defense: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    (
+        "B56",
+        "Sicilian Defense: Classical Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6",
+    ),
+    (
+        "B60",
+        "Sicilian Defense: Richter-Rauzer Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6 Bg5",
+    ),
+    (
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    (
+        "B76",
+        "Sicilian Defense: Dragon Variation, Yugoslav Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3",
+    ),
+    (
+        "B80",
+        "Sicilian Defense: Scheveningen Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6",
+    ),
+    (
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    (
+        "B90",
+        "Sicilian Defense: Najdorf Variation, English Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3",
+    ),
+    ("C00", "French Defense", "e4 e6"),
+    (
+        "C01",
+        "French Defense: Exchange Variation",
+        "e4 e6 d4 d5 exd5",
+    ),
+    ("C02", "French Defense: Advance Variation", "e4 e6 d4 d5 e5"),
+    (
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e4 e6 d4 d5 Nd2",
+    ),
+    (
+        "C10",
+        "French Defense: Paulsen Variation",
+        "e4 e6 d4 d5 Nc3",
+    ),
+    (
+        "C10",
+        "French Defense: Rubinstein Variation",
+        "e4 e6 d4 d5 Nc3 dxe4",
+    ),
+    (
+        "C11",
+        "French Defense: Classical Variation",
+        "e4 e6 d4 d5 Nc3 Nf6",
+    ),
+    (
+        "C11",
+        "French Defense: Steinitz Variation",
+        "e4 e6 d4 d5 Nc3 Nf6 e5",
+    ),
+    (
+        "C15",
+        "French Defense: Winawer Variation",
+        "e4 e6 d4 d5 Nc3 Bb4",
+    ),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C21", "Danish Gambit", "e4 e5 d4 exd4 c3"),
+    ("C22", "Center Game", "e4 e5 d4 exd4 Qxd4"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C29", "Vienna Gambit", "e4 e5 Nc3 Nf6 f4"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    (
+        "C31",
+        "King's Gambit Declined: Falkbeer Countergambit",
+        "e4 e5 f4 d5",
+    ),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C40", "Latvian Gambit", "e4 e5 Nf3 f5"),
+    ("C40", "Elephant Gambit", "e4 e5 Nf3 d5"),
+    ("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    (
+        "C42",
+        "Petrov's Defense: Classical Attack",
+        "e4 e5 Nf3 Nf6 Nxe5 d6 Nf3 Nxe4 d4",
+    ),
+    (
+        "C43",
+        "Petrov's Defense: Steinitz Attack",
+        "e4 e5 Nf3 Nf6 d4",
+    ),
+    (
+        "C44",
+        "King's Knight Opening: Normal Variation",
+        "e4 e5 Nf3 Nc6",
+    ),
+    ("C44", "Ponziani Opening", "e4 e5 Nf3 Nc6 c3"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C44", "Scotch Gambit", "e4 e5 Nf3 Nc6 d4 exd4 Bc4"),
+    (
+        "C45",
+        "Scotch Game: Main Line",
+        "e4 e5 Nf3 Nc6 d4 exd4 Nxd4",
+    ),
+    ("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    (
+        "C47",
+        "Four Knights Game: Scotch Variation",
+        "e4 e5 Nf3 Nc6 Nc3 Nf6 d4",
+    ),
+    (
+        "C48",
+        "Four Knights Game: Spanish Variation",
+        "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5",
+    ),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    (
+        "C50",
+        "Italian Game: Hungarian Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Be7",
+    ),
+    ("C50", "Italian Game: Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    (
+        "C51",
+        "Italian Game: Evans Gambit",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 b4",
+    ),
+    (
+        "C53",
+        "Italian Game: Classical Variation",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 c3",
+    ),
+    (
+        "C55",
+        "Italian Game: Two Knights Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6",
+    ),
+    (
+        "C57",
+        "Italian Game: Two Knights Defense, Knight Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5",
+    ),
+    (
+        "C57",
+        "Italian Game: Two Knights Defense, Traxler Counterattack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 Bc5",
+    ),
+    (
+        "C57",
+        "Italian Game: Two Knights Defense, Fried Liver Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 d5 exd5 Nxd5 Nxf7",
+    ),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C60", "Ruy Lopez: Morphy Defense", "e4 e5 Nf3 Nc6 Bb5 a6"),
+    ("C62", "Ruy Lopez: Steinitz Defense", "e4 e5 Nf3 Nc6 Bb5 d6"),
+    (
+        "C63",
+        "Ruy Lopez: Schliemann Defense",
+        "e4 e5 Nf3 Nc6 Bb5 f5",
+    ),
+    (
+        "C64",
+        "Ruy Lopez: Classical Variation",
+        "e4 e5 Nf3 Nc6 Bb5 Bc5",
+    ),
+    ("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    (
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    (
+        "C80",
+        "Ruy Lopez: Open",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4",
+    ),
+    (
+        "C84",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    (
+        "C89",
+        "Ruy Lopez: Marshall Attack",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5",
+    ),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D00", "Blackmar-Diemer Gambit", "d4 d5 e4"),
+    ("D00", "London System", "d4 d5 Bf4"),
+    ("D01", "Richter-Veresov Attack", "d4 d5 Nc3 Nf6 Bg5"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    (
+        "D07",
+        "Queen's Gambit Declined: Chigorin Defense",
+        "d4 d5 c4 Nc6",
+    ),
+    (
+        "D08",
+        "Queen's Gambit Declined: Albin Countergambit",
+        "d4 d5 c4 e5",
+    ),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D32", "Tarrasch Defense", "d4 d5 c4 e6 Nc3 c5"),
+    (
+        "D35",
+        "Queen's Gambit Declined: Exchange Variation",
+        "d4 d5 c4 e6 Nc3 Nf6 cxd5",
+    ),
+    ("D43", "Semi-Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6"),
+    ("D80", "Grunfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    (
+        "D85",
+        "Grunfeld Defense: Exchange Variation",
+        "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5 e4 Nxc3 bxc3",
+    ),
+    ("E00", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    ("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4"),
+    ("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    (
+        "E32",
+        "Nimzo-Indian Defense: Classical Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 Qc2",
+    ),
+    (
+        "E40",
+        "Nimzo-Indian Defense: Rubinstein Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 e3",
+    ),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    (
+        "E70",
+        "King's Indian Defense: Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6",
+    ),
+    (
+        "E76",
+        "King's Indian Defense: Four Pawns Attack",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4",
+    ),
+    (
+        "E80",
+        "King's Indian Defense: Samisch Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3",
+    ),
+    (
+        "E92",
+        "King's Indian Defense: Classical Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5",
+    ),
+    (
+        "E97",
+        "King's Indian Defense: Mar del Plata Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7",
+    ),
+];
+
+/// The name of the opening of the given position, if it is one of `OPENINGS`. Transpositions
+/// are recognized, as the positions are compared rather than the moves.
+pub fn classify_opening(board: &Board) -> Option<&'static str> {
+    find_opening(board).map(|(_, name, _)| *name)
+}
+
+/// The ECO code of the opening of the given position, if it is one of `OPENINGS`.
+pub fn eco_code(board: &Board) -> Option<&'static str> {
+    find_opening(board).map(|(eco, _, _)| *eco)
+}
+
+fn find_opening(board: &Board) -> Option<&'static (&'static str, &'static str, &'static str)> {
+    opening_positions()
+        .get(&board.get_hash())
+        .map(|&index| &OPENINGS[index])
+}
+
+/// The indices into `OPENINGS` by the hashes of their positions, computed once.
+fn opening_positions() -> &'static HashMap<u64, usize> {
+    static POSITIONS: OnceLock<HashMap<u64, usize>> = OnceLock::new();
+    POSITIONS.get_or_init(|| {
+        OPENINGS
+            .iter()
+            .enumerate()
+            .map(|(index, (_, _, moves))| {
+                let sans: Vec<&str> = moves.split_whitespace().collect();
+                let board = HistoryBoard::from_sans(&sans).expect("the openings are legal");
+                (board.get_hash(), index)
+            })
+            .collect()
+    })
+}
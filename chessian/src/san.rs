@@ -0,0 +1,93 @@
+use chess::{Board, BoardStatus, ChessMove, File, MoveGen, Piece, Rank, Square};
+
+/// The given legal move on the given board in standard algebraic notation, e.g. `Nbd7`, `exd6`,
+/// `e8=Q+` or `O-O-O#`.
+pub fn move_to_san(m: ChessMove, board: &Board) -> String {
+    let (source, dest) = (m.get_source(), m.get_dest());
+    let Some(piece) = board.piece_on(source) else {
+        return m.to_string();
+    };
+    let mut san = String::new();
+    if piece == Piece::King
+        && source
+            .get_file()
+            .to_index()
+            .abs_diff(dest.get_file().to_index())
+            == 2
+    {
+        san.push_str(if dest.get_file() == File::G {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else {
+        // a pawn moving to another file captures, maybe en passant
+        let is_capture = board.piece_on(dest).is_some()
+            || (piece == Piece::Pawn && source.get_file() != dest.get_file());
+        if piece == Piece::Pawn {
+            if is_capture {
+                san.push(file_char(source.get_file()));
+            }
+        } else {
+            san.push(piece_char(piece));
+            san.push_str(&disambiguation(m, piece, board));
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&dest.to_string());
+        if let Some(promotion) = m.get_promotion() {
+            san.push('=');
+            san.push(piece_char(promotion));
+        }
+    }
+    let after = board.make_move_new(m);
+    if after.status() == BoardStatus::Checkmate {
+        san.push('#');
+    } else if after.checkers().popcnt() > 0 {
+        san.push('+');
+    }
+    san
+}
+
+/// The file, rank or square of the source of the given move of the given piece that tells it
+/// apart from the other moves of a piece of its type to the same square, if there are any.
+fn disambiguation(m: ChessMove, piece: Piece, board: &Board) -> String {
+    let source = m.get_source();
+    let others: Vec<Square> = MoveGen::new_legal(board)
+        .filter(|other| {
+            other.get_dest() == m.get_dest()
+                && other.get_source() != source
+                && board.piece_on(other.get_source()) == Some(piece)
+        })
+        .map(|other| other.get_source())
+        .collect();
+    if others.is_empty() {
+        String::new()
+    } else if others.iter().all(|s| s.get_file() != source.get_file()) {
+        file_char(source.get_file()).to_string()
+    } else if others.iter().all(|s| s.get_rank() != source.get_rank()) {
+        rank_char(source.get_rank()).to_string()
+    } else {
+        source.to_string()
+    }
+}
+
+fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+fn file_char(file: File) -> char {
+    (b'a' + file.to_index() as u8) as char
+}
+
+fn rank_char(rank: Rank) -> char {
+    (b'1' + rank.to_index() as u8) as char
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chess::*;
+use shakmaty::{CastlingMode, Chess};
+use shakmaty_syzygy::{AmbiguousWdl, Tablebase};
+
+use crate::book::shakmaty_position;
+use crate::chooser::MATE_SCORE;
+use crate::error::ChessianError;
+
+/// The score of a position that the tablebases declare won, below any mate score found by the
+/// search.
+pub const TB_WIN_SCORE: i32 = MATE_SCORE / 2;
+
+/// The outcome of a position with perfect play, from the side to move's perspective.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WdlResult {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// The search only consults tablebases in positions with at most this many pieces.
+pub const TB_MAX_PIECES: u32 = 6;
+
+/// A source of perfect endgame play, e.g. Syzygy tablebases. All methods return `None` for
+/// positions the provider doesn't cover.
+pub trait TablebaseProvider: fmt::Debug + Send + Sync {
+    /// The outcome of the given board with perfect play.
+    fn probe_wdl(&self, board: &Board) -> Option<WdlResult>;
+
+    /// The number of plies until the fifty-move counter is zeroed with perfect play, negative if
+    /// the side to move loses.
+    fn probe_dtz(&self, board: &Board) -> Option<i32>;
+
+    /// The move to play in the given board with perfect play.
+    fn best_move(&self, _board: &Board) -> Option<ChessMove> {
+        None
+    }
+}
+
+/// A provider that knows no positions, for running without tablebases.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopTablebase;
+
+impl TablebaseProvider for NoopTablebase {
+    fn probe_wdl(&self, _board: &Board) -> Option<WdlResult> {
+        None
+    }
+
+    fn probe_dtz(&self, _board: &Board) -> Option<i32> {
+        None
+    }
+}
+
+/// Probes Syzygy endgame tablebases (WDL and DTZ tables) from a directory.
+pub struct TablebaseProber {
+    path: String,
+    tables: Tablebase<Chess>,
+}
+
+impl TablebaseProber {
+    /// Loads all tables from the given directory.
+    pub fn new(path: &str) -> Result<Self, ChessianError> {
+        let mut tables = Tablebase::new();
+        tables.add_directory(path)?;
+        Ok(Self {
+            path: String::from(path),
+            tables,
+        })
+    }
+
+    /// Whether the board has few enough pieces to be covered by the loaded tables.
+    pub fn covers(&self, board: &Board) -> bool {
+        board.combined().popcnt() as usize <= self.tables.max_pieces()
+    }
+}
+
+impl TablebaseProvider for TablebaseProber {
+    /// Positions that are only won or lost disregarding the fifty-move rule count as draws.
+    fn probe_wdl(&self, board: &Board) -> Option<WdlResult> {
+        if !self.covers(board) {
+            return None;
+        }
+        match self.tables.probe_wdl(&shakmaty_position(board)?).ok()? {
+            AmbiguousWdl::Win | AmbiguousWdl::MaybeWin => Some(WdlResult::Win),
+            AmbiguousWdl::Loss | AmbiguousWdl::MaybeLoss => Some(WdlResult::Loss),
+            AmbiguousWdl::CursedWin | AmbiguousWdl::BlessedLoss | AmbiguousWdl::Draw => {
+                Some(WdlResult::Draw)
+            }
+        }
+    }
+
+    fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        let dtz = self.tables.probe_dtz(&shakmaty_position(board)?).ok()?;
+        Some(dtz.ignore_rounding().0)
+    }
+
+    /// The move that preserves the outcome while zeroing the fifty-move counter as fast as
+    /// possible according to the DTZ tables.
+    fn best_move(&self, board: &Board) -> Option<ChessMove> {
+        if !self.covers(board) {
+            return None;
+        }
+        let (m, _dtz) = self.tables.best_move(&shakmaty_position(board)?).ok()??;
+        ChessMove::from_str(&m.to_uci(CastlingMode::Standard).to_string()).ok()
+    }
+}
+
+impl WdlResult {
+    /// The search score of a position with this outcome.
+    pub fn score(self) -> i32 {
+        match self {
+            Self::Win => TB_WIN_SCORE,
+            Self::Draw => 0,
+            Self::Loss => -TB_WIN_SCORE,
+        }
+    }
+}
+
+impl fmt::Debug for TablebaseProber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TablebaseProber")
+            .field("path", &self.path)
+            .finish()
+    }
+}
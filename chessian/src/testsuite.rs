@@ -1,10 +1,22 @@
 use crate::chooser::*;
+use crate::timecontrol::*;
 use crate::*;
+use chess::{Board, ChessMove};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+
+/// The Eigenmann rapid engine test suite in EPD format, embedded so that it can be run without
+/// the data file being present.
+pub const EIGENMANN_EPD: &str = include_str!("../eigenmann.txt");
 
 pub struct TestCase {
     pub board: HistoryBoard,
-    pub solution: ChessMove,
+    /// The best moves (`bm`), any of which solves the case.
+    pub solutions: Vec<ChessMove>,
+    /// The moves to avoid (`am`), none of which may be played to solve the case.
+    pub avoid: Vec<ChessMove>,
     pub id: String,
 }
 
@@ -15,45 +27,206 @@ pub fn load_test_suite(src: &str) -> Vec<TestCase> {
 impl TestCase {
     // r1bqk1r1/1p1p1n2/p1n2pN1/2p1b2Q/2P1Pp2/1PN5/PB4PP/R4RK1 w q - - bm Rxf4; id "ERET 001 - Relief";
     pub fn parse(line: &str) -> Result<Self, String> {
-        let bm_idx = line
-            .find("bm")
-            .or_else(|| line.find("am"))
-            .ok_or_else(|| format!("missing `bm` in '{line}'"))?;
-        let semi_idx = line
-            .find(";")
-            .ok_or_else(|| format!("missing `;` in '{line}'"))?;
-        let fen = &line[0..bm_idx];
-        let solution_str = &line[bm_idx + 3..semi_idx];
-        let id_str = &line[semi_idx + 6..line.len() - 2];
-        let board = Board::from_str(fen).map_err(|e| format!("{e}"))?;
+        let mut fields = line.splitn(5, ' ');
+        let fen = fields.by_ref().take(4).collect::<Vec<_>>().join(" ");
+        let operations = fields
+            .next()
+            .ok_or_else(|| format!("missing operations in '{line}'"))?;
+        let board = Board::from_str(&fen).map_err(|e| format!("{e}"))?;
+        let parse_moves = |operands: Vec<&str>| {
+            operands
+                .into_iter()
+                .map(|san| ChessMove::from_san(&board, san).map_err(|e| format!("{e}")))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let mut solutions = Vec::new();
+        let mut avoid = Vec::new();
+        let mut id = String::new();
+        for operation in operations.split(';') {
+            // skip the optional halfmove clock and fullmove number fields
+            let mut tokens = operation
+                .split_whitespace()
+                .skip_while(|t| *t == "-" || t.parse::<u32>().is_ok());
+            let opcode = tokens.next();
+            let operands = tokens.collect::<Vec<_>>();
+            match opcode {
+                Some("bm") => solutions = parse_moves(operands)?,
+                Some("am") => avoid = parse_moves(operands)?,
+                Some("id") => id = operands.join(" ").trim_matches('"').to_string(),
+                _otherwise => (),
+            }
+        }
+        if solutions.is_empty() && avoid.is_empty() {
+            return Err(format!("missing `bm` or `am` in '{line}'"));
+        }
         Ok(Self {
             board: HistoryBoard::new(board),
-            solution: ChessMove::from_san(&board, solution_str).map_err(|e| format!("{e}"))?,
-            id: String::from(id_str),
+            solutions,
+            avoid,
+            id,
         })
     }
-}
 
-pub fn eigenmann() -> usize {
-    let src = std::fs::read_to_string("eigenmann.txt").expect("eigenmann.txt missing");
-    let test_suite = load_test_suite(&src);
-    let mut score = 0;
-    for case in &test_suite {
-        println!("--- {} ---", case.id);
-        let engine_move = chooser::best_move(
-            &case.board,
-            TimeControl::new(None, TCMode::MoveTime(15_000)),
-            &[],
-            std::io::stdout(),
+    /// Whether playing the given move solves this case.
+    pub fn is_solved_by(&self, m: ChessMove) -> bool {
+        (self.solutions.is_empty() || self.solutions.contains(&m)) && !self.avoid.contains(&m)
+    }
+
+    /// Searches the position of this case for the given time.
+    pub fn run(&self, time_ms: u128) -> TestCaseResult {
+        let result = chooser::best_move(
+            &self.board,
+            TimeControl::new(None, TCMode::MoveTime(time_ms)),
+            &ChooserConfig {
+                tt: Some(Arc::new(TranspositionTable::new(DEFAULT_TT_SIZE_MB))),
+                ..ChooserConfig::default()
+            },
+            std::io::sink(),
             std::io::sink(),
         )
-        .unwrap()
-        .best_move;
-        println!("    solution: {}", case.solution);
-        println!("    engine: {engine_move}");
-        if case.solution == engine_move {
-            score += 1;
+        .expect("test positions have legal moves");
+        TestCaseResult {
+            case_id: self.id.clone(),
+            expected: self.solutions.clone(),
+            played: result.best_move,
+            passed: self.is_solved_by(result.best_move),
+            eval_cp: result.deep_eval,
+            depth: result.reached_depth,
+            nodes: result.stats.node_count,
+            millis: result.millis,
+        }
+    }
+}
+
+/// The outcome of running the engine on a single test case.
+#[derive(Clone, Debug)]
+pub struct TestCaseResult {
+    pub case_id: String,
+    /// The solutions of the case.
+    pub expected: Vec<ChessMove>,
+    /// The move the engine played.
+    pub played: ChessMove,
+    pub passed: bool,
+    /// The evaluation of the played move from the perspective of the side to move.
+    pub eval_cp: i32,
+    pub depth: usize,
+    pub nodes: u64,
+    pub millis: u128,
+}
+
+impl TestCaseResult {
+    /// Serializes the result as a JSON object.
+    pub fn to_json(&self) -> String {
+        let expected = self
+            .expected
+            .iter()
+            .map(|m| format!("\"{m}\""))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"case_id\":\"{}\",\"expected\":[{expected}],\"played\":\"{}\",\"passed\":{},\"eval_cp\":{},\"depth\":{},\"nodes\":{},\"millis\":{}}}",
+            self.case_id.replace('\\', "\\\\").replace('"', "\\\""),
+            self.played,
+            self.passed,
+            self.eval_cp,
+            self.depth,
+            self.nodes,
+            self.millis
+        )
+    }
+}
+
+/// Aggregate figures of the results of a test suite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SuiteSummary {
+    /// The fraction of passed cases, from 0 to 1.
+    pub pass_rate: f64,
+    /// The average evaluation of the played moves of the failed cases.
+    pub avg_eval_failed: f64,
+    pub avg_depth: f64,
+}
+
+/// Summarizes the given results.
+pub fn suite_summary(results: &[TestCaseResult]) -> SuiteSummary {
+    let average = |values: Vec<f64>| {
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
         }
+    };
+    let passed = results.iter().filter(|r| r.passed).count();
+    SuiteSummary {
+        pass_rate: passed as f64 / results.len().max(1) as f64,
+        avg_eval_failed: average(
+            results
+                .iter()
+                .filter(|r| !r.passed)
+                .map(|r| r.eval_cp as f64)
+                .collect(),
+        ),
+        avg_depth: average(results.iter().map(|r| r.depth as f64).collect()),
     }
-    score
+}
+
+/// Runs the Eigenmann rapid engine test suite, searching the given number of cases in parallel
+/// for the given time each.
+pub fn eigenmann(threads: usize, time_per_case_ms: u128) -> Vec<TestCaseResult> {
+    let test_suite = load_test_suite(EIGENMANN_EPD);
+    let next_case = AtomicUsize::new(0);
+    let node_count = AtomicU64::new(0);
+    let millis = AtomicU64::new(0);
+
+    let mut results: Vec<(usize, TestCaseResult)> = thread::scope(|s| {
+        let (test_suite, next_case) = (&test_suite, &next_case);
+        let (node_count, millis) = (&node_count, &millis);
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                s.spawn(move || {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next_case.fetch_add(1, Ordering::Relaxed);
+                        let Some(case) = test_suite.get(index) else {
+                            break results;
+                        };
+                        let result = case.run(time_per_case_ms);
+                        node_count.fetch_add(result.nodes, Ordering::Relaxed);
+                        millis.fetch_add(result.millis as u64, Ordering::Relaxed);
+                        let mut report = format!("--- {} ---\n", case.id);
+                        for solution in &case.solutions {
+                            report.push_str(&format!("    solution: {solution}\n"));
+                        }
+                        for avoid in &case.avoid {
+                            report.push_str(&format!("    avoid: {avoid}\n"));
+                        }
+                        report.push_str(&format!("    engine: {}", result.played));
+                        println!("{report}");
+                        results.push((index, result));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+
+    let node_count = node_count.into_inner();
+    let millis = millis.into_inner();
+    println!(
+        "nodes {node_count} time {millis} nps {:.0}",
+        node_count as f64 / (millis.max(1) as f64 / 1000.0)
+    );
+    let results: Vec<_> = results.into_iter().map(|(_, result)| result).collect();
+    let summary = suite_summary(&results);
+    println!(
+        "passed {:.1}% avg depth {:.1} avg eval of failed {:.0}",
+        summary.pass_rate * 100.0,
+        summary.avg_depth,
+        summary.avg_eval_failed
+    );
+    results
 }
@@ -7,34 +7,107 @@ use std::sync::{
 pub struct TimeControl {
     stop_flag: Option<Arc<AtomicBool>>,
     mode: TCMode,
+    /// While set, the search is pondering and ignores its limits until stopped.
+    ponder_flag: Option<Arc<AtomicBool>>,
+    /// After this many milliseconds, no new iteration of the search is started.
+    pub soft_limit_ms: Option<u128>,
+    /// After this many milliseconds, the search is aborted immediately.
+    pub hard_limit_ms: Option<u128>,
 }
 
+/// The depth searched to by `TCMode::Bench`.
+pub const BENCH_DEPTH: usize = 6;
+
 #[derive(Clone, Debug)]
 pub enum TCMode {
     MoveTime(u128),
     Depth(usize),
+    /// Playing with the given remaining time and increment per move, in milliseconds.
+    GameClock {
+        time: u128,
+        increment: u128,
+    },
     Infinite,
+    /// Search to `BENCH_DEPTH`, used to compare the speed of builds.
+    Bench,
 }
 
 impl TimeControl {
     pub fn new(stop_flag: Option<Arc<AtomicBool>>, mode: TCMode) -> Self {
-        Self { stop_flag, mode }
+        let (soft_limit_ms, hard_limit_ms) = match mode {
+            TCMode::MoveTime(millis) => (Some(millis), Some(millis)),
+            TCMode::GameClock { time, increment } => {
+                let soft = time / 20 + increment / 2;
+                // never use more than half of the remaining time on a single move
+                (Some(soft), Some((soft * 3).min(time / 2)))
+            }
+            TCMode::Depth(_) | TCMode::Infinite | TCMode::Bench => (None, None),
+        };
+        Self {
+            stop_flag,
+            mode,
+            ponder_flag: None,
+            soft_limit_ms,
+            hard_limit_ms,
+        }
+    }
+
+    /// Makes the search ponder, i.e. search on the opponent's time, as long as the given flag is
+    /// set. Once it is cleared (ponderhit), the limits apply as measured from the start of the
+    /// search, so that the time spent pondering is a head start.
+    pub fn with_ponder_flag(mut self, ponder_flag: Arc<AtomicBool>) -> Self {
+        self.ponder_flag = Some(ponder_flag);
+        self
     }
 
+    /// Reduces the time limits by the given milliseconds, e.g. to make up for the latency of the
+    /// communication with a GUI, but at most to half of them.
+    pub fn with_move_overhead(mut self, overhead_ms: u128) -> Self {
+        let reduce = |limit: u128| limit - overhead_ms.min(limit / 2);
+        self.soft_limit_ms = self.soft_limit_ms.map(reduce);
+        self.hard_limit_ms = self.hard_limit_ms.map(reduce);
+        self
+    }
+
+    /// Whether to not start another iteration of the search after the given time and depth.
     pub fn should_stop(&self, elapsed: u128, reached_depth: usize) -> bool {
-        if self
-            .stop_flag
-            .as_ref()
-            .map(|b| b.load(Ordering::Relaxed))
-            .unwrap_or(false)
-        {
-            true
-        } else {
-            match self.mode {
-                TCMode::MoveTime(millis) => elapsed >= millis,
+        if self.is_stopped() {
+            return true;
+        }
+        if self.is_pondering() {
+            return false;
+        }
+        self.soft_limit_ms.is_some_and(|limit| elapsed >= limit)
+            || match self.mode {
                 TCMode::Depth(depth) => reached_depth >= depth,
-                TCMode::Infinite => false,
+                TCMode::Bench => reached_depth >= BENCH_DEPTH,
+                TCMode::MoveTime(_) | TCMode::GameClock { .. } | TCMode::Infinite => false,
             }
+    }
+
+    /// Whether to abort the current iteration of the search after the given time.
+    pub fn should_abort(&self, elapsed: u128) -> bool {
+        if self.is_stopped() {
+            return true;
         }
+        !self.is_pondering() && self.hard_limit_ms.is_some_and(|limit| elapsed >= limit)
+    }
+
+    /// Whether a search that finished early, e.g. because it found a mate, must hold back its
+    /// result until it is stopped or, when pondering, the ponder flag is cleared.
+    pub fn awaits_stop(&self) -> bool {
+        !self.is_stopped() && (self.is_pondering() || matches!(self.mode, TCMode::Infinite))
+    }
+
+    fn is_pondering(&self) -> bool {
+        self.ponder_flag
+            .as_ref()
+            .is_some_and(|b| b.load(Ordering::Relaxed))
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop_flag
+            .as_ref()
+            .is_some_and(|b| b.load(Ordering::Relaxed))
     }
 }
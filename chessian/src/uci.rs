@@ -0,0 +1,23 @@
+use chess::{ChessMove, Piece, Square};
+
+/// The given move in the long algebraic notation of UCI: the source and destination squares,
+/// followed by the lowercase promotion piece if any, e.g. `e2e4`, `e1g1` or `a7a8q`.
+pub fn move_to_uci(m: ChessMove) -> String {
+    let mut uci = String::with_capacity(5);
+    push_square(&mut uci, m.get_source());
+    push_square(&mut uci, m.get_dest());
+    if let Some(promotion) = m.get_promotion() {
+        uci.push(match promotion {
+            Piece::Knight => 'n',
+            Piece::Bishop => 'b',
+            Piece::Rook => 'r',
+            Piece::Queen | Piece::Pawn | Piece::King => 'q',
+        });
+    }
+    uci
+}
+
+fn push_square(uci: &mut String, square: Square) {
+    uci.push((b'a' + square.get_file().to_index() as u8) as char);
+    uci.push((b'1' + square.get_rank().to_index() as u8) as char);
+}
@@ -0,0 +1,34 @@
+use std::str::FromStr;
+
+use chess::{Board, MoveGen};
+use chessian::HistoryBoard;
+use chessian::chooser::*;
+use chessian::timecontrol::*;
+
+fn history_board(fen: &str) -> HistoryBoard {
+    HistoryBoard::new(Board::from_str(fen).unwrap())
+}
+
+#[test]
+fn random_chooser_chooses_legal_moves() {
+    let board = history_board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    for _ in 0..20 {
+        let result = RandomChooser
+            .choose(&board, TimeControl::new(None, TCMode::Depth(1)))
+            .unwrap();
+        assert!(board.legal(result.best_move));
+    }
+}
+
+#[test]
+fn choosers_find_no_move_when_mated() {
+    let board = history_board("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+    assert_eq!(MoveGen::new_legal(&board.board).len(), 0);
+    let time_control = TimeControl::new(None, TCMode::Depth(1));
+    assert!(RandomChooser.choose(&board, time_control.clone()).is_none());
+    assert!(
+        AlphaBetaChooser::default()
+            .choose(&board, time_control)
+            .is_none()
+    );
+}
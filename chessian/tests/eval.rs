@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use chess::{Board, ChessMove, Color};
+use chessian::HistoryBoard;
+use chessian::eval::*;
+
+/// The difference the connected rooks bonus makes to the evaluation of the given position.
+fn connected_rooks_term(fen: &str) -> i32 {
+    let board = Board::from_str(fen).unwrap();
+    let without_bonus = EvalConfig {
+        connected_rooks_bonus: 0,
+        ..EvalConfig::DEFAULT
+    };
+    eval(&board) - eval_with_config(&board, &without_bonus)
+}
+
+#[test]
+fn connected_rooks_on_open_file() {
+    assert_eq!(
+        connected_rooks_term("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1"),
+        CONNECTED_ROOKS_BONUS
+    );
+    assert_eq!(
+        connected_rooks_term("r3k3/8/8/r7/8/8/8/4K3 w - - 0 1"),
+        -CONNECTED_ROOKS_BONUS
+    );
+}
+
+#[test]
+fn blocked_rooks_are_not_connected() {
+    assert_eq!(connected_rooks_term("4k3/8/8/8/R7/8/N7/R3K3 w - - 0 1"), 0);
+    assert_eq!(connected_rooks_term("4k3/8/8/8/R7/8/8/1R2K3 w - - 0 1"), 0);
+}
+
+#[test]
+fn cached_eval_matches_full_eval() {
+    // captures, en passant and castling on both sides
+    let moves = "e2e4 d7d5 e4e5 f7f5 e5f6 g8f6 g1f3 e7e6 f1c4 f8c5 e1g1 e8g8 c4d5 e6d5";
+    let moves: Vec<_> = moves
+        .split_whitespace()
+        .map(|m| ChessMove::from_str(m).unwrap())
+        .collect();
+    for plies in 0..=moves.len() {
+        let board = HistoryBoard::from_moves(&moves[..plies]).unwrap();
+        assert_eq!(
+            eval_with_cache(&board),
+            eval(&board.board),
+            "after {plies} plies"
+        );
+    }
+}
+
+#[test]
+fn mating_net_drives_the_lone_king_to_the_edge() {
+    let bonus = |fen| mating_net_bonus(&Board::from_str(fen).unwrap());
+    let center = bonus("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+    let edge = bonus("3k4/8/8/8/8/8/8/R3K3 w - - 0 1");
+    let edge_kings_close = bonus("3k4/8/3K4/8/8/8/8/R7 w - - 0 1");
+    assert!(center > 0);
+    assert!(edge > center);
+    assert!(edge_kings_close > edge);
+    assert!(bonus("3K4/8/8/8/8/8/8/q3k3 w - - 0 1") < 0);
+    assert_eq!(bonus("3k4/3p4/8/8/8/8/8/R3K3 w - - 0 1"), 0);
+}
+
+#[test]
+fn side_to_move_gets_the_tempo_bonus() {
+    let white = Board::from_str("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap();
+    let black = Board::from_str("4k3/pppp4/8/8/8/8/PPPP4/4K3 b - - 0 1").unwrap();
+    assert_eq!(eval(&white) - eval(&black), 2 * TEMPO_BONUS);
+}
+
+#[test]
+fn trapped_bishop_is_penalized() {
+    // the bishop on a7 can only go to b8, which the own rook occupies, or b6, which c7 defends
+    let board = Board::from_str("1R6/B1p4k/1p6/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        trapped_piece_penalty(&board, Color::White),
+        TRAPPED_PIECE_PENALTY
+    );
+    assert_eq!(trapped_piece_penalty(&board, Color::Black), 0);
+    // undeveloped pieces are not trapped
+    let start = Board::default();
+    assert_eq!(trapped_piece_penalty(&start, Color::White), 0);
+    assert_eq!(trapped_piece_penalty(&start, Color::Black), 0);
+}
+
+#[test]
+fn castling_rights_and_uncastled_kings() {
+    let start = Board::default();
+    assert_eq!(
+        castling_score(&start, Color::White),
+        2 * CASTLING_AVAILABLE_BONUS
+    );
+    let king_walked =
+        Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPKPPP/RNBQ1BNR b kq - 1 1").unwrap();
+    assert_eq!(
+        castling_score(&king_walked, Color::White),
+        -UNCASTLED_PENALTY
+    );
+    let castled =
+        Board::from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1").unwrap();
+    assert_eq!(castling_score(&castled, Color::White), 0);
+    // not in the endgame
+    let endgame = Board::from_str("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap();
+    assert_eq!(castling_score(&endgame, Color::White), 0);
+}
+
+#[test]
+fn undefended_piece_attacked_by_a_pawn_is_hanging() {
+    let hanging = Board::from_str("4k3/8/8/4p3/3N4/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        threat_eval(&hanging, Color::White),
+        KNIGHT_VALUE * HANGING_PIECE_PENALTY_PERCENT / 100
+    );
+    assert_eq!(threat_eval(&hanging, Color::Black), 0);
+    // the pawn on c3 defends the knight
+    let defended = Board::from_str("4k3/8/8/4p3/3N4/2P5/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(threat_eval(&defended, Color::White), 0);
+}
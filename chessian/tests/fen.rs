@@ -0,0 +1,44 @@
+use chessian::*;
+
+fn error_kind(fen: &str) -> Option<FenParseErrorKind> {
+    parse_fen(fen).err().map(|e| e.kind)
+}
+
+#[test]
+fn fen_errors_name_the_malformed_field() {
+    use FenParseErrorKind::*;
+    assert_eq!(error_kind("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), None);
+    assert_eq!(error_kind("4k3/8/8/8/8/8/8/4K3 w - -"), None);
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/4K3 w - - 0 1"),
+        Some(InvalidPiecePlacement)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/8 w - - 0 1"),
+        Some(InvalidPiecePlacement)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/4K3 x - - 0 1"),
+        Some(InvalidSideToMove)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/4K3 w KK - 0 1"),
+        Some(InvalidCastlingRights)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/4K3 w - e4 0 1"),
+        Some(InvalidEnPassant)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/4K3 w - - x 1"),
+        Some(InvalidHalfmoveClock)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/4K3 w - - 0 0"),
+        Some(InvalidFullmoveNumber)
+    );
+    assert_eq!(
+        error_kind("4k3/8/8/8/8/8/8/4K3 w"),
+        Some(InvalidCastlingRights)
+    );
+}
@@ -0,0 +1,31 @@
+use std::str::FromStr;
+
+use chess::Board;
+use chessian::*;
+
+fn assert_round_trip(fen: &str) {
+    let board = Board::from_str(fen).unwrap();
+    let written = board_to_fen(&board);
+    let reparsed = Board::from_str(&written).unwrap();
+    assert_eq!(
+        reparsed.get_hash(),
+        board.get_hash(),
+        "{fen} became {written}"
+    );
+    assert_eq!(parse_fen(&written), Ok(board), "{fen} became {written}");
+}
+
+#[test]
+fn fen_round_trip() {
+    // the starting position
+    assert_round_trip("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    // some castling rights
+    assert_round_trip("r3k2r/pppq1ppp/2n2n2/3pp3/3PP3/2N2N2/PPPQ1PPP/R3K2R b Kq - 4 8");
+    // en passant squares for both sides
+    assert_round_trip("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    assert_round_trip("rnbqkbnr/pppp1ppp/8/8/3PpP2/8/PPP1P1PP/RNBQKBNR b KQkq f3 0 3");
+    // all castling rights lost
+    assert_round_trip("r4rk1/pp3ppp/8/8/8/8/PP3PPP/2KR3R w - - 6 20");
+    // an endgame
+    assert_round_trip("8/5k2/3p4/1p1P4/1P6/4K3/8/8 w - - 0 45");
+}
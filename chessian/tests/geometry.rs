@@ -0,0 +1,14 @@
+use chess::Square;
+use chessian::geometry::*;
+
+#[test]
+fn distances_of_known_squares() {
+    assert_eq!(chebyshev_distance(Square::E4, Square::E4), 0);
+    assert_eq!(manhattan_distance(Square::E4, Square::E4), 0);
+    assert_eq!(chebyshev_distance(Square::A1, Square::H8), 7);
+    assert_eq!(manhattan_distance(Square::A1, Square::H8), 14);
+    assert_eq!(chebyshev_distance(Square::B1, Square::C3), 2);
+    assert_eq!(manhattan_distance(Square::B1, Square::C3), 3);
+    assert_eq!(chebyshev_distance(Square::H2, Square::A2), 7);
+    assert_eq!(manhattan_distance(Square::D8, Square::E1), 8);
+}
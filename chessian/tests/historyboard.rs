@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::hash::{BuildHasher, RandomState};
+use std::str::FromStr;
+
+use chess::*;
+use chessian::*;
+
+#[test]
+fn from_sans_plays_the_moves() {
+    let board = HistoryBoard::from_sans(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O"]).unwrap();
+    let expected =
+        Board::from_str("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4")
+            .unwrap();
+    assert_eq!(board.board, expected);
+    assert_eq!(board.halfmove_clock, 5);
+}
+
+#[test]
+fn apply_moves_san_rejects_illegal_moves() {
+    let board = HistoryBoard::from_sans(&["e4", "e5"]).unwrap();
+    assert!(board.apply_moves_san(&["Nf3", "Ke7", "Ke2"]).is_ok());
+    assert!(matches!(
+        board.apply_moves_san(&["Nf3", "Nc6", "O-O"]),
+        Err(ChessianError::InvalidMove(san)) if san == "O-O"
+    ));
+}
+
+#[test]
+fn fullmove_number_counts_from_the_start() {
+    let board = HistoryBoard::from_sans(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O"]).unwrap();
+    assert_eq!(board.move_count(), 7);
+    assert_eq!(
+        board.to_fen(),
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4"
+    );
+    let black_to_move = Board::from_str("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+    let board = HistoryBoard::new(black_to_move)
+        .apply_moves_san(&["Kd7", "Kd2"])
+        .unwrap();
+    assert_eq!(board.to_fen(), "8/3k4/8/8/8/8/3K4/8 b - - 2 2");
+}
+
+fn history_board(fen: &str) -> HistoryBoard {
+    HistoryBoard::new(Board::from_str(fen).unwrap())
+}
+
+#[test]
+fn insufficient_material_is_a_draw() {
+    // bishops on c1 and f8, both dark squares
+    let same_color_bishops = history_board("5b2/8/4k3/8/8/3K4/8/2B5 w - - 0 1");
+    assert!(same_color_bishops.is_insufficient_material());
+    assert!(same_color_bishops.is_draw());
+    assert_eq!(same_color_bishops.status(), BoardStatus::Stalemate);
+    assert!(history_board("5n2/8/4k3/8/8/3K4/8/2N5 w - - 0 1").is_insufficient_material());
+    assert!(history_board("8/8/4k3/8/8/3K4/8/2B5 w - - 0 1").is_insufficient_material());
+    // bishops on c1 and c8, a dark and a light square
+    let opposite_bishops = history_board("2b5/8/4k3/8/8/3K4/8/2B5 w - - 0 1");
+    assert!(!opposite_bishops.is_insufficient_material());
+    assert_eq!(opposite_bishops.status(), BoardStatus::Ongoing);
+    assert!(!history_board("8/8/4k3/8/8/3K4/8/2R5 w - - 0 1").is_insufficient_material());
+    assert!(!history_board("8/8/4k3/8/8/3K4/8/1NB5 w - - 0 1").is_insufficient_material());
+}
+
+#[test]
+fn fifty_move_rule_is_a_draw() {
+    let mut board = history_board("8/8/4k3/8/8/3K4/8/2R5 w - - 0 1");
+    board.halfmove_clock = 99;
+    assert!(!board.is_draw());
+    board.halfmove_clock = 100;
+    assert!(board.is_draw());
+}
+
+#[test]
+fn takeback_mut_restores_the_previous_position() {
+    let start = HistoryBoard::from_sans(&["e4", "e5"]).unwrap();
+    let mut board = start.clone();
+    for san in ["Nf3", "Nc6", "Ng1", "Nb8"] {
+        board.make_move_mut(ChessMove::from_san(&board.board, san).unwrap());
+    }
+    assert_eq!(board.history[&start.board.get_hash()], 2);
+    while board.takeback_mut() {}
+    assert_eq!(board, start);
+    assert_eq!(board.halfmove_clock, start.halfmove_clock);
+    assert_eq!(board.eval_cache, start.eval_cache);
+    assert_eq!(board.move_count(), start.move_count());
+}
+
+#[test]
+fn from_fen_keeps_the_counters() {
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4";
+    let board = HistoryBoard::from_fen(fen).unwrap();
+    assert_eq!(board.halfmove_clock, 5);
+    assert_eq!(board.to_fen(), fen);
+    let board = board.apply_moves_san(&["Be7", "Re1"]).unwrap();
+    assert_eq!(board.fullmove_number(), 5);
+    assert_eq!(board.halfmove_clock, 7);
+    // without counters they start at 0 and 1
+    let board = HistoryBoard::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+    assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+}
+
+#[test]
+fn null_move_passes_the_turn() {
+    let board = HistoryBoard::from_sans(&["e4", "d5", "e5", "f5"]).unwrap();
+    assert_eq!(board.en_passant(), Some(Square::F5));
+    let passed = board.make_null_move().unwrap();
+    assert_eq!(passed.side_to_move(), Color::Black);
+    assert_eq!(passed.en_passant(), None);
+    assert_eq!(passed.history, board.history);
+    let in_check = HistoryBoard::from_sans(&["e4", "f5", "Qh5"]).unwrap();
+    assert!(in_check.make_null_move().is_none());
+}
+
+#[test]
+fn boards_with_the_same_moves_are_equal() {
+    let sans = ["e4", "e5", "Nf3", "Nc6", "Bb5"];
+    let first = HistoryBoard::new(Board::default())
+        .apply_moves_san(&sans)
+        .unwrap();
+    let second = HistoryBoard::new(Board::default())
+        .apply_moves_san(&sans)
+        .unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn repetitions_make_boards_unequal_with_equal_hashes() {
+    let once = HistoryBoard::from_sans(&["Nf3", "Nf6"]).unwrap();
+    let twice = once.apply_moves_san(&["Ng1", "Ng8", "Nf3", "Nf6"]).unwrap();
+    assert_eq!(once.board, twice.board);
+    assert_ne!(once, twice);
+    let hasher = RandomState::new();
+    assert_eq!(hasher.hash_one(&once), hasher.hash_one(&twice));
+    let set: HashSet<_> = [once, twice].into_iter().collect();
+    assert_eq!(set.len(), 2);
+}
@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+use chess::*;
+use chessian::chooser::{order_moves, see};
+use chessian::is_en_passant;
+
+#[test]
+fn winning_capture_comes_first() {
+    let board = Board::from_str("3k4/8/8/3r4/8/8/8/3QK3 w - - 0 1").unwrap();
+    let mut moves: Vec<_> = MoveGen::new_legal(&board).collect();
+    order_moves(&mut moves, &board);
+    assert_eq!(moves[0], ChessMove::new(Square::D1, Square::D5, None));
+}
+
+#[test]
+fn see_of_exchanges() {
+    // the pawn on d5 is defended by the pawn on e6
+    let board = Board::from_str("3k4/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    let qxd5 = ChessMove::new(Square::D1, Square::D5, None);
+    assert_eq!(see(&qxd5, &board), 100 - 900);
+    // undefended
+    let board = Board::from_str("3k4/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    assert_eq!(see(&qxd5, &board), 100);
+    // the rook behind the queen wins the exchange on d5
+    let board = Board::from_str("3k4/3r4/8/3r4/8/8/3R4/3QK3 w - - 0 1").unwrap();
+    let rxd5 = ChessMove::new(Square::D2, Square::D5, None);
+    assert_eq!(see(&rxd5, &board), 500);
+}
+
+#[test]
+fn en_passant_captures_a_pawn() {
+    let board = Board::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    let exd6 = ChessMove::new(Square::E5, Square::D6, None);
+    let e6 = ChessMove::new(Square::E5, Square::E6, None);
+    assert!(is_en_passant(exd6, &board));
+    assert!(!is_en_passant(e6, &board));
+    assert_eq!(see(&exd6, &board), 100);
+}
+
+#[test]
+fn ordering_does_not_depend_on_generation_order() {
+    let board = Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+        .unwrap();
+    let mut moves: Vec<_> = MoveGen::new_legal(&board).collect();
+    let mut reversed: Vec<_> = moves.iter().rev().copied().collect();
+    order_moves(&mut moves, &board);
+    order_moves(&mut reversed, &board);
+    assert_eq!(moves, reversed);
+}
@@ -0,0 +1,36 @@
+use std::str::FromStr;
+
+use chess::Board;
+use chessian::HistoryBoard;
+use chessian::chooser::*;
+use chessian::timecontrol::*;
+
+fn multipv(fen: &str, lines: usize) -> Vec<ChooserResult> {
+    let board = HistoryBoard::new(Board::from_str(fen).unwrap());
+    best_move_multipv(
+        &board,
+        lines,
+        TimeControl::new(None, TCMode::Depth(2)),
+        &ChooserConfig::default(),
+    )
+}
+
+#[test]
+fn lines_are_distinct_and_best_first() {
+    let results = multipv(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        3,
+    );
+    assert_eq!(results.len(), 3);
+    assert_ne!(results[0].best_move, results[1].best_move);
+    assert_ne!(results[0].best_move, results[2].best_move);
+    assert_ne!(results[1].best_move, results[2].best_move);
+    assert!(results[0].deep_eval >= results[1].deep_eval);
+    assert!(results[1].deep_eval >= results[2].deep_eval);
+}
+
+#[test]
+fn no_more_lines_than_legal_moves() {
+    // the king can only take the rook
+    assert_eq!(multipv("k7/8/8/8/8/8/1r6/K7 w - - 0 1", 3).len(), 1);
+}
@@ -0,0 +1,31 @@
+use chess::Board;
+use chessian::openings::*;
+use chessian::*;
+
+fn play(moves: &str) -> Board {
+    let sans: Vec<&str> = moves.split_whitespace().collect();
+    HistoryBoard::from_sans(&sans).unwrap().board
+}
+
+#[test]
+fn every_opening_is_recognized() {
+    for (eco, name, moves) in OPENINGS {
+        let board = play(moves);
+        assert_eq!(classify_opening(&board), Some(*name), "{moves}");
+        assert_eq!(eco_code(&board), Some(*eco), "{moves}");
+    }
+}
+
+#[test]
+fn transpositions_are_recognized() {
+    assert_eq!(
+        classify_opening(&play("d4 Nf6 Nf3 e6 c4 b6")),
+        Some("Queen's Indian Defense")
+    );
+}
+
+#[test]
+fn unknown_positions_have_no_name() {
+    assert_eq!(classify_opening(&Board::default()), None);
+    assert_eq!(classify_opening(&play("h4 h5")), None);
+}
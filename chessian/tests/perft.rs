@@ -0,0 +1,80 @@
+use std::str::FromStr;
+
+use chess::*;
+use chessian::HistoryBoard;
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3P4/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+/// Counts the leaf nodes of the move tree of the given depth.
+fn perft(board: Board, depth: usize) -> u64 {
+    if depth == 0 {
+        1
+    } else if depth == 1 {
+        MoveGen::new_legal(&board).len() as u64
+    } else {
+        MoveGen::new_legal(&board)
+            .map(|m| perft(board.make_move_new(m), depth - 1))
+            .sum()
+    }
+}
+
+/// Lists each legal move with the node count of its subtree, to narrow down perft discrepancies.
+fn perft_divide(board: Board, depth: usize) -> Vec<(ChessMove, u64)> {
+    MoveGen::new_legal(&board)
+        .map(|m| (m, perft(board.make_move_new(m), depth - 1)))
+        .collect()
+}
+
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let board = Board::from_str(fen).unwrap();
+    for (depth, nodes) in expected.iter().enumerate() {
+        assert_eq!(
+            perft(board, depth + 1),
+            *nodes,
+            "perft {} of {fen}",
+            depth + 1
+        );
+    }
+}
+
+#[test]
+fn perft_start_position() {
+    assert_perft(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902, 197_281, 4_865_609],
+    );
+}
+
+#[test]
+fn perft_kiwipete() {
+    assert_perft(KIWIPETE, &[48, 2039, 97_862, 4_085_603]);
+}
+
+#[test]
+fn perft_position_3() {
+    assert_perft(POSITION_3, &[14, 191, 2812, 43_238, 674_624]);
+}
+
+#[test]
+fn perft_divide_sums_to_perft() {
+    let board = Board::from_str(KIWIPETE).unwrap();
+    let divide = perft_divide(board, 3);
+    assert_eq!(divide.len(), 48);
+    assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), 97_862);
+}
+
+#[test]
+fn history_board_perft_matches_board_perft() {
+    for fen in [KIWIPETE, POSITION_3] {
+        let board = Board::from_str(fen).unwrap();
+        let history_board = HistoryBoard::new(board);
+        for depth in 0..=3 {
+            assert_eq!(
+                history_board.perft(depth),
+                perft(board, depth),
+                "perft {depth} of {fen}"
+            );
+        }
+    }
+}
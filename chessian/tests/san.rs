@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+use chess::{Board, ChessMove};
+use chessian::*;
+
+fn san(fen: &str, uci: &str) -> String {
+    let board = Board::from_str(fen).unwrap();
+    move_to_san(ChessMove::from_str(uci).unwrap(), &board)
+}
+
+#[test]
+fn pawn_and_piece_moves() {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(san(start, "e2e4"), "e4");
+    assert_eq!(san(start, "g1f3"), "Nf3");
+    let scandinavian = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+    assert_eq!(san(scandinavian, "e4d5"), "exd5");
+}
+
+#[test]
+fn castling() {
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(san(fen, "e1g1"), "O-O");
+    assert_eq!(san(fen, "e1c1"), "O-O-O");
+}
+
+#[test]
+fn disambiguation() {
+    // knights on b1 and f3 can both reach d2
+    assert_eq!(san("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "b1d2"), "Nbd2");
+    // rooks on a1 and a5 can both reach a3
+    assert_eq!(san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+    // queens on a1, a4 and d1 can all reach d4 and share the file or rank of the one on a1
+    assert_eq!(san("8/7k/8/8/Q7/8/8/Q2QK3 w - - 0 1", "a1d4"), "Qa1d4");
+}
+
+#[test]
+fn en_passant_and_promotion() {
+    let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+    assert_eq!(san(fen, "e5f6"), "exf6");
+    assert_eq!(san("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), "a8=Q");
+    assert_eq!(san("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8n"), "axb8=N");
+}
+
+#[test]
+fn check_and_mate() {
+    assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8+");
+    assert_eq!(san("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8#");
+}
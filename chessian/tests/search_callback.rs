@@ -0,0 +1,39 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use chess::Board;
+use chessian::HistoryBoard;
+use chessian::chooser::*;
+use chessian::timecontrol::*;
+
+#[test]
+fn callback_is_called_for_each_depth() {
+    let board = HistoryBoard::new(
+        Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+            .unwrap(),
+    );
+    let depths = Arc::new(Mutex::new(Vec::new()));
+    let reported = Arc::clone(&depths);
+    let config = ChooserConfig {
+        book: None,
+        threads: 1,
+        on_depth_complete: Some(SearchDepthCallback(Arc::new(move |depth, _, m| {
+            reported.lock().unwrap().push((depth, m));
+        }))),
+        ..ChooserConfig::default()
+    };
+    let result = best_move(
+        &board,
+        TimeControl::new(None, TCMode::Depth(3)),
+        &config,
+        std::io::sink(),
+        std::io::sink(),
+    )
+    .unwrap();
+    let depths = depths.lock().unwrap();
+    assert_eq!(
+        depths.iter().map(|(depth, _)| *depth).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(depths.last().unwrap().1, result.best_move);
+}
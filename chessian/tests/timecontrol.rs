@@ -0,0 +1,30 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use chessian::timecontrol::{TCMode, TimeControl};
+
+#[test]
+fn infinite_search_awaits_stop() {
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let time_control = TimeControl::new(Some(stop_flag.clone()), TCMode::Infinite);
+    assert!(time_control.awaits_stop());
+    stop_flag.store(true, Ordering::Relaxed);
+    assert!(!time_control.awaits_stop());
+}
+
+#[test]
+fn pondering_search_awaits_ponderhit() {
+    let ponder_flag = Arc::new(AtomicBool::new(true));
+    let time_control =
+        TimeControl::new(None, TCMode::MoveTime(1_000)).with_ponder_flag(ponder_flag.clone());
+    assert!(time_control.awaits_stop());
+    ponder_flag.store(false, Ordering::Relaxed);
+    assert!(!time_control.awaits_stop());
+}
+
+#[test]
+fn depth_search_does_not_await_stop() {
+    assert!(!TimeControl::new(None, TCMode::Depth(3)).awaits_stop());
+}
@@ -0,0 +1,22 @@
+use chess::{ChessMove, Piece, Square};
+use chessian::move_to_uci;
+
+#[test]
+fn moves_in_long_algebraic_notation() {
+    assert_eq!(
+        move_to_uci(ChessMove::new(Square::E2, Square::E4, None)),
+        "e2e4"
+    );
+    assert_eq!(
+        move_to_uci(ChessMove::new(Square::E1, Square::G1, None)),
+        "e1g1"
+    );
+    assert_eq!(
+        move_to_uci(ChessMove::new(Square::A7, Square::A8, Some(Piece::Queen))),
+        "a7a8q"
+    );
+    assert_eq!(
+        move_to_uci(ChessMove::new(Square::H2, Square::G1, Some(Piece::Knight))),
+        "h2g1n"
+    );
+}
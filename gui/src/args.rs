@@ -0,0 +1,67 @@
+/// The usage of the command line arguments.
+pub const USAGE: &str = "\
+usage: chessian-gui [options]
+
+options:
+    --fen <fen>        start from the given position instead of the initial one
+    --pgn <path>       continue the game of the given PGN file, instead of --fen
+    --moves <moves>    play the given space separated moves in UCI notation first
+    --time <ms>        how long the computer searches per move
+    --depth <n>        search to the given depth instead of for a fixed time
+    --black            play as black, so that the computer only plays white
+    --pieces <png>     use the piece sprites (16 pixels in size) from the given file
+                       instead of the built-in ones
+    --go               print the best move in the position and exit instead of
+                       opening the window
+    --help             print this message";
+
+/// The command line arguments of the gui.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub fen: Option<String>,
+    pub pgn: Option<String>,
+    pub moves: Vec<String>,
+    pub time: Option<u128>,
+    pub depth: Option<usize>,
+    pub black: bool,
+    pub pieces: Option<String>,
+    pub go: bool,
+    pub help: bool,
+}
+
+impl Args {
+    /// Parses the given arguments, excluding the name of the program.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut result = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--fen" => result.fen = Some(value()?),
+                "--pgn" => result.pgn = Some(value()?),
+                "--moves" => {
+                    result.moves = value()?.split_whitespace().map(String::from).collect();
+                }
+                "--time" => result.time = Some(parse_number(&arg, value()?)?),
+                "--depth" => result.depth = Some(parse_number(&arg, value()?)?),
+                "--black" => result.black = true,
+                "--pieces" => result.pieces = Some(value()?),
+                "--go" => result.go = true,
+                "--help" => result.help = true,
+                _otherwise => return Err(format!("unknown argument {arg}\n\n{USAGE}")),
+            }
+        }
+        if result.fen.is_some() && result.pgn.is_some() {
+            return Err(String::from("--fen and --pgn can't be combined"));
+        }
+        Ok(result)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number for {arg}, got {value}"))
+}
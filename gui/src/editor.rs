@@ -0,0 +1,107 @@
+use std::fmt;
+
+use chess::{ALL_FILES, Board, BoardBuilder, CastleRights, Color, File, Piece, Square};
+
+use chessian::HistoryBoard;
+
+/// The order in which clicking a square cycles through its contents.
+const PIECE_CYCLE: [Option<(Piece, Color)>; 13] = [
+    None,
+    Some((Piece::Pawn, Color::White)),
+    Some((Piece::Knight, Color::White)),
+    Some((Piece::Bishop, Color::White)),
+    Some((Piece::Rook, Color::White)),
+    Some((Piece::Queen, Color::White)),
+    Some((Piece::King, Color::White)),
+    Some((Piece::Pawn, Color::Black)),
+    Some((Piece::Knight, Color::Black)),
+    Some((Piece::Bishop, Color::Black)),
+    Some((Piece::Rook, Color::Black)),
+    Some((Piece::Queen, Color::Black)),
+    Some((Piece::King, Color::Black)),
+];
+
+/// The labels for choosing the en passant file, the first meaning none.
+pub const EN_PASSANT_LABELS: [&str; 9] = ["-", "a", "b", "c", "d", "e", "f", "g", "h"];
+
+/// A position that is being set up in the editor, which doesn't have to be legal until it is
+/// loaded.
+pub struct EditorState {
+    builder: BoardBuilder,
+}
+
+impl EditorState {
+    pub fn new(board: &HistoryBoard) -> Self {
+        Self {
+            builder: BoardBuilder::from(&board.board),
+        }
+    }
+
+    pub fn piece_on(&self, square: Square) -> Option<(Piece, Color)> {
+        self.builder[square]
+    }
+
+    /// Replaces the contents of the given square with the next ones in `PIECE_CYCLE`.
+    pub fn cycle(&mut self, square: Square) {
+        let current = self.builder[square];
+        let index = PIECE_CYCLE.iter().position(|p| *p == current).unwrap_or(0);
+        match PIECE_CYCLE[(index + 1) % PIECE_CYCLE.len()] {
+            Some((piece, color)) => self.builder.piece(square, piece, color),
+            None => self.builder.clear_square(square),
+        };
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.builder.get_side_to_move()
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.builder.side_to_move(color);
+    }
+
+    /// Whether the given color may castle king side and queen side.
+    pub fn castle_rights(&self, color: Color) -> (bool, bool) {
+        let rights = self.builder.get_castle_rights(color);
+        (rights.has_kingside(), rights.has_queenside())
+    }
+
+    pub fn set_castle_rights(&mut self, color: Color, kingside: bool, queenside: bool) {
+        let rights = match (kingside, queenside) {
+            (true, true) => CastleRights::Both,
+            (true, false) => CastleRights::KingSide,
+            (false, true) => CastleRights::QueenSide,
+            (false, false) => CastleRights::NoRights,
+        };
+        self.builder.castle_rights(color, rights);
+    }
+
+    /// The file of the pawn that may be captured en passant.
+    pub fn en_passant(&self) -> Option<File> {
+        self.builder
+            .get_en_passant()
+            .map(|square| square.get_file())
+    }
+
+    pub fn set_en_passant(&mut self, file: Option<File>) {
+        self.builder.en_passant(file);
+    }
+
+    /// The position set up in the editor, if it is valid.
+    pub fn board(&self) -> Result<Board, String> {
+        Board::try_from(&self.builder)
+            .ok()
+            .filter(Board::is_sane)
+            .ok_or_else(|| String::from("the position is not valid"))
+    }
+}
+
+impl fmt::Debug for EditorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EditorState({})", self.builder)
+    }
+}
+
+/// The en passant file for the given index into `EN_PASSANT_LABELS`.
+pub fn en_passant_file(index: usize) -> Option<File> {
+    index.checked_sub(1).map(|i| ALL_FILES[i])
+}
@@ -1,38 +1,170 @@
-use std::str::FromStr;
-
 use chess::*;
 
 use chessian::chooser::*;
 use chessian::historyboard::HistoryBoard;
 use chessian::timecontrol::*;
+use chessian::{ChessianError, FenParseError, classify_opening, move_to_san, move_to_uci};
+
+use crate::gametree::GameTree;
+
+/// The evaluation drop, in centipawns, from which on a move is a mistake.
+pub const MISTAKE_EVAL_DROP: i32 = 100;
+/// The evaluation drop, in centipawns, from which on a move is a blunder.
+pub const BLUNDER_EVAL_DROP: i32 = 200;
+
+/// A judgement of the quality of a move, as used in game analysis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveAnnotation {
+    Brilliant,
+    Good,
+    Interesting,
+    Dubious,
+    Mistake,
+    Blunder,
+}
+
+impl MoveAnnotation {
+    /// All annotations from the best to the worst.
+    pub const ALL: [Self; 6] = [
+        Self::Brilliant,
+        Self::Good,
+        Self::Interesting,
+        Self::Dubious,
+        Self::Mistake,
+        Self::Blunder,
+    ];
+
+    /// The symbol the move is suffixed with, e.g. `??` for a blunder.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Brilliant => "!!",
+            Self::Good => "!",
+            Self::Interesting => "!?",
+            Self::Dubious => "?!",
+            Self::Mistake => "?",
+            Self::Blunder => "??",
+        }
+    }
+
+    /// The annotation with the given symbol, if any.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.symbol() == symbol)
+    }
+
+    /// The annotation of a move that worsened the evaluation of the side that made it by the
+    /// given number of centipawns, if the move was bad enough to be annotated.
+    pub fn from_eval_drop(drop: i32) -> Option<Self> {
+        if drop > BLUNDER_EVAL_DROP {
+            Some(Self::Blunder)
+        } else if drop > MISTAKE_EVAL_DROP {
+            Some(Self::Mistake)
+        } else {
+            None
+        }
+    }
+}
 
+/// The outcome of a finished game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+#[derive(Clone)]
 pub struct GameState {
-    board: HistoryBoard,
+    tree: GameTree,
+    /// The nodes of the line being viewed, including the undone moves.
+    line: Vec<usize>,
+    /// The number of moves of `line` that are played on the board.
+    ply: usize,
     legal_moves: Vec<ChessMove>,
-    undo_queue: Vec<(HistoryBoard, ChessMove)>,
-    redo_queue: Vec<(HistoryBoard, ChessMove)>,
+    /// Moves the engine must not choose in the current position, to see its alternatives.
+    exclude_moves: Vec<ChessMove>,
     last_move: Option<ChessMove>,
+    /// The move the engine made last, until another move is made or the position changes.
+    last_engine_move: Option<ChessMove>,
 }
 
 impl GameState {
     pub fn from_board(board: Board) -> Self {
+        Self::from_history_board(HistoryBoard::new(board))
+    }
+
+    pub fn from_history_board(board: HistoryBoard) -> Self {
         Self {
-            board: HistoryBoard::new(board),
-            legal_moves: MoveGen::new_legal(&board).collect(),
-            undo_queue: Vec::new(),
-            redo_queue: Vec::new(),
+            legal_moves: MoveGen::new_legal(&board.board).collect(),
+            tree: GameTree::new(board),
+            line: Vec::new(),
+            ply: 0,
+            exclude_moves: Vec::new(),
             last_move: None,
+            last_engine_move: None,
         }
     }
 
-    pub fn from_fen(fen: &str) -> Result<Self, String> {
-        Board::from_str(fen)
-            .map(Self::from_board)
-            .map_err(|e| format!("{e}"))
+    pub fn from_fen(fen: &str) -> Result<Self, FenParseError> {
+        HistoryBoard::from_fen(fen).map(Self::from_history_board)
+    }
+
+    /// Reads a game from PGN, see `GameTree::from_pgn`, positioned at the end of its main line.
+    pub fn from_pgn(pgn: &str) -> Result<Self, ChessianError> {
+        let tree = GameTree::from_pgn(pgn)?;
+        let line = tree.main_line();
+        let mut state = Self::from_history_board(tree.root().clone());
+        state.last_move = line.last().map(|n| tree.node(*n).move_played);
+        state.ply = line.len();
+        state.line = line;
+        state.tree = tree;
+        state.get_legal_moves();
+        Ok(state)
+    }
+
+    /// The game with all its variations as PGN, see `GameTree::to_pgn_with_variations`.
+    pub fn to_pgn(&self) -> String {
+        self.tree.to_pgn_with_variations()
+    }
+
+    /// Starts over from the given position, discarding all moves, unlike `undo_all`, which
+    /// returns to the position the game started from.
+    pub fn set_initial_position(&mut self, board: HistoryBoard) {
+        self.tree = GameTree::new(board);
+        self.line.clear();
+        self.ply = 0;
+        self.exclude_moves.clear();
+        self.last_move = None;
+        self.last_engine_move = None;
+        self.get_legal_moves();
     }
 
     pub fn board(&self) -> &HistoryBoard {
-        &self.board
+        self.tree.board(self.current_node())
+    }
+
+    /// The game with all its variations.
+    pub fn tree(&self) -> &GameTree {
+        &self.tree
+    }
+
+    /// The node of the last move played on the board, `None` in the starting position.
+    pub fn current_node(&self) -> Option<usize> {
+        self.ply.checked_sub(1).map(|i| self.line[i])
+    }
+
+    /// The number of moves played on the board.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// The number of halfmoves since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u8 {
+        self.board().halfmove_clock
+    }
+
+    /// The position the game started from.
+    pub fn initial_board(&self) -> &HistoryBoard {
+        self.tree.root()
     }
 
     pub fn legal_moves_from(&self, square: Square) -> Vec<ChessMove> {
@@ -43,22 +175,60 @@ impl GameState {
             .collect()
     }
 
+    pub fn legal_moves_to(&self, dest: Square) -> Vec<ChessMove> {
+        self.legal_moves
+            .iter()
+            .filter(|m| m.get_dest() == dest)
+            .cloned()
+            .collect()
+    }
+
+    /// The legal moves of the current position in standard algebraic notation.
+    pub fn legal_moves_san(&self) -> Vec<String> {
+        self.legal_moves
+            .iter()
+            .map(|m| move_to_san(*m, self.board()))
+            .collect()
+    }
+
+    /// The legal moves of the current position in UCI notation, in the order of
+    /// `legal_moves_san`.
+    pub fn legal_moves_uci(&self) -> Vec<String> {
+        self.legal_moves.iter().map(|m| move_to_uci(*m)).collect()
+    }
+
+    /// Makes the given move. Unless it is the next move of the line, it starts a variation, and
+    /// the undone moves remain in the game tree.
     pub fn make_move(&mut self, m: ChessMove) {
-        self.undo_queue.push((self.board.clone(), m));
-        self.redo_queue.clear();
-        self.board = self.board.make_move(m);
+        let node = match self.current_node() {
+            Some(current) => self.tree.branch_at(current, m),
+            None => self.tree.branch_at_root(m),
+        };
+        if self.line.get(self.ply) != Some(&node) {
+            // a variation that was played before continues with its main line
+            self.line.truncate(self.ply);
+            self.line.push(node);
+            self.line.extend(self.tree.line_from(Some(node)));
+        }
+        self.ply += 1;
+        self.exclude_moves.clear();
         self.get_legal_moves();
         self.last_move = Some(m);
+        self.last_engine_move = None;
+    }
+
+    /// Makes the given move in UCI notation, e.g. `e2e4` or `a7a8q`.
+    pub fn make_move_uci(&mut self, uci: &str) -> Result<(), ChessianError> {
+        let m = self.board().parse_uci_move(uci)?;
+        self.make_move(m);
+        Ok(())
     }
 
-    pub fn engine_move(&mut self, time_control: TimeControl) -> Option<ChooserResult> {
-        if let Some(result) = best_move(
-            &self.board,
-            time_control,
-            std::io::stdout(),
-            std::io::sink(),
-        ) {
+    /// Makes the move of the given result of a search of the current position, if any.
+    pub fn make_engine_move(&mut self, result: Option<ChooserResult>) -> Option<ChooserResult> {
+        if let Some(result) = result {
             self.make_move(result.best_move);
+            self.last_engine_move = Some(result.best_move);
             if let Some(r) = result.response {
                 println!("I'm predicting {r}");
             }
@@ -68,12 +238,45 @@ impl GameState {
         }
     }
 
+    /// Plays the game out from the current position, the given choosers moving for white and
+    /// black with the given time control each move, until it is over or a chooser finds no move,
+    /// which counts as a draw. `on_move` is called after each move.
+    pub fn play_game<CW: Chooser, CB: Chooser>(
+        &mut self,
+        white: &CW,
+        black: &CB,
+        time_control: &TimeControl,
+        on_move: Option<&dyn Fn(&GameState)>,
+    ) -> GameResult {
+        loop {
+            match self.board().status() {
+                BoardStatus::Checkmate if self.board().side_to_move() == Color::White => {
+                    return GameResult::BlackWins;
+                }
+                BoardStatus::Checkmate => return GameResult::WhiteWins,
+                BoardStatus::Stalemate => return GameResult::Draw,
+                BoardStatus::Ongoing => (),
+            }
+            let result = match self.board().side_to_move() {
+                Color::White => white.choose(self.board(), time_control.clone()),
+                Color::Black => black.choose(self.board(), time_control.clone()),
+            };
+            let Some(result) = result else {
+                return GameResult::Draw;
+            };
+            self.make_move(result.best_move);
+            if let Some(on_move) = on_move {
+                on_move(self);
+            }
+        }
+    }
+
     pub fn undo_move(&mut self) -> bool {
-        if let Some((b, m)) = self.undo_queue.pop() {
-            self.redo_queue
-                .push((self.board.clone(), self.last_move.unwrap()));
-            self.board = b;
-            self.last_move = Some(m);
+        if self.can_undo() {
+            self.last_move = Some(self.tree.node(self.line[self.ply - 1]).move_played);
+            self.ply -= 1;
+            self.exclude_moves.clear();
+            self.last_engine_move = None;
             self.get_legal_moves();
             true
         } else {
@@ -82,11 +285,11 @@ impl GameState {
     }
 
     pub fn redo_move(&mut self) -> bool {
-        if let Some((b, m)) = self.redo_queue.pop() {
-            self.undo_queue
-                .push((self.board.clone(), self.last_move.unwrap()));
-            self.board = b;
-            self.last_move = Some(m);
+        if self.can_redo() {
+            self.ply += 1;
+            self.last_move = Some(self.tree.node(self.line[self.ply - 1]).move_played);
+            self.exclude_moves.clear();
+            self.last_engine_move = None;
             self.get_legal_moves();
             true
         } else {
@@ -94,17 +297,257 @@ impl GameState {
         }
     }
 
-    pub fn history(&self) -> &Vec<(HistoryBoard, ChessMove)> {
-        &self.undo_queue
+    pub fn can_undo(&self) -> bool {
+        self.ply > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.ply < self.line.len()
+    }
+
+    /// Undoes all moves, returning false if there were none.
+    pub fn undo_all(&mut self) -> bool {
+        let any = self.can_undo();
+        while self.undo_move() {}
+        any
+    }
+
+    /// Redoes all undone moves, returning false if there were none.
+    pub fn redo_all(&mut self) -> bool {
+        let any = self.can_redo();
+        while self.redo_move() {}
+        any
+    }
+
+    /// Navigates to the position after the `index`th move of the line, 0 being the starting
+    /// position. Returns false if the line has no such position.
+    pub fn position_at(&mut self, index: usize) -> bool {
+        if index > self.line.len() {
+            return false;
+        }
+        self.ply = index;
+        self.last_move = self.current_node().map(|n| self.tree.node(n).move_played);
+        self.exclude_moves.clear();
+        self.last_engine_move = None;
+        self.get_legal_moves();
+        true
+    }
+
+    /// Navigates to the position after the move of the given node, following the main line of
+    /// the variation from there on.
+    pub fn go_to_node(&mut self, node: usize) {
+        self.line = self.tree.path_to(node);
+        let index = self.line.len();
+        self.line.extend(self.tree.line_from(Some(node)));
+        self.position_at(index);
+    }
+
+    /// Returns to the main line of the game, at the last position it shares with the current
+    /// line.
+    pub fn go_to_main_line(&mut self) {
+        let main_line = self.tree.main_line();
+        let common = self
+            .line
+            .iter()
+            .zip(&main_line)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.line = main_line;
+        self.position_at(self.ply.min(common));
+    }
+
+    /// The nodes of the moves of the line, including those that were undone.
+    pub fn line(&self) -> &[usize] {
+        &self.line
+    }
+
+    /// The moves the engine must not choose in the current position.
+    pub fn exclude_moves(&self) -> &[ChessMove] {
+        &self.exclude_moves
+    }
+
+    /// Forbids the engine to choose the given move in the current position.
+    pub fn exclude_move(&mut self, m: ChessMove) {
+        if !self.exclude_moves.contains(&m) {
+            self.exclude_moves.push(m);
+        }
+    }
+
+    pub fn clear_exclude_moves(&mut self) {
+        self.exclude_moves.clear();
+    }
+
+    /// Calls `f` with the given move excluded like with `exclude_move`, excluding it no longer
+    /// afterwards, even if `f` panics, unless it was excluded before.
+    pub fn exclude_move_temporary<T>(&mut self, m: ChessMove, f: impl FnOnce(&mut Self) -> T) -> T {
+        let mut guard = TemporaryExclusion {
+            excluded: (!self.exclude_moves.contains(&m)).then_some(m),
+            state: self,
+        };
+        if let Some(m) = guard.excluded {
+            guard.state.exclude_moves.push(m);
+        }
+        f(&mut *guard.state)
+    }
+
+    /// The annotation of the `index`th move of the line, if any.
+    pub fn annotation(&self, index: usize) -> Option<MoveAnnotation> {
+        self.line
+            .get(index)
+            .and_then(|n| self.tree.node(*n).annotation)
+    }
+
+    /// Annotates the `index`th move of the line, if it exists.
+    pub fn annotate_move(&mut self, index: usize, annotation: MoveAnnotation) {
+        if let Some(&node) = self.line.get(index) {
+            self.tree.annotate(node, Some(annotation));
+        }
+    }
+
+    /// Removes the annotation of the `index`th move of the line.
+    pub fn remove_annotation(&mut self, index: usize) {
+        if let Some(&node) = self.line.get(index) {
+            self.tree.annotate(node, None);
+        }
+    }
+
+    /// Annotates the mistakes and blunders among the moves of the line by comparing searches to
+    /// the given depth of the positions before and after each move. `progress` is called with the
+    /// number of moves analyzed so far and the total number.
+    pub fn auto_annotate(&mut self, depth: usize, mut progress: impl FnMut(usize, usize)) {
+        let ply = self.ply;
+        let count = self.line.len();
+        for i in 0..count {
+            self.position_at(i);
+            let m = self.tree.node(self.line[i]).move_played;
+            if let Some(annotation) =
+                MoveAnnotation::from_eval_drop(-self.delta_move_eval(m, depth))
+            {
+                self.annotate_move(i, annotation);
+            }
+            progress(i + 1, count);
+        }
+        self.position_at(ply);
+    }
+
+    /// The change of the evaluation by the given move in the current position, according to
+    /// searches to the given depth before and after it. It is positive if the move improves the
+    /// position of the side making it, which it can't if the search finds the best move.
+    pub fn delta_move_eval(&self, m: ChessMove, depth: usize) -> i32 {
+        delta_move_eval(self.board(), m, depth)
+    }
+
+    /// The annotations of the moves of the line, with the hashes of the positions they were
+    /// played in, e.g. to copy them to another game state with `annotate_moves`.
+    pub fn line_annotations(&self) -> Vec<(u64, ChessMove, MoveAnnotation)> {
+        self.line
+            .iter()
+            .filter_map(|n| {
+                let node = self.tree.node(*n);
+                let board = self.tree.board(node.parent);
+                node.annotation
+                    .map(|annotation| (board.get_hash(), node.move_played, annotation))
+            })
+            .collect()
+    }
+
+    /// Annotates the given moves wherever they were played in the positions with the given
+    /// hashes, which still works if the game changed since they were found.
+    pub fn annotate_moves(&mut self, annotations: &[(u64, ChessMove, MoveAnnotation)]) {
+        for (hash, m, annotation) in annotations {
+            self.tree.annotate_in_position(*hash, *m, *annotation);
+        }
+    }
+
+    /// The boards of all positions before the current one, from the first.
+    pub fn history_boards(&self) -> impl Iterator<Item = &HistoryBoard> {
+        std::iter::once(None)
+            .chain(self.line.iter().copied().map(Some))
+            .take(self.ply)
+            .map(|n| self.tree.board(n))
+    }
+
+    /// The moves played up to the current position, each with the board it was played on.
+    pub fn history(&self) -> impl Iterator<Item = (&HistoryBoard, ChessMove)> {
+        self.history_boards().zip(
+            self.line[..self.ply]
+                .iter()
+                .map(|&n| self.tree.node(n).move_played),
+        )
+    }
+
+    /// The name of the opening of the current position or, once the game has left the known
+    /// openings, of the last position that had one.
+    pub fn opening_name(&self) -> Option<&'static str> {
+        let history: Vec<&HistoryBoard> = self.history_boards().collect();
+        std::iter::once(self.board())
+            .chain(history.into_iter().rev())
+            .find_map(|board| classify_opening(&board.board))
+    }
+
+    /// The FENs of all positions before the current one, from the first.
+    pub fn history_fens(&self) -> Vec<String> {
+        self.history_boards().map(HistoryBoard::to_fen).collect()
     }
 
     pub fn get_legal_moves(&mut self) {
-        self.legal_moves = MoveGen::new_legal(&self.board.board).collect();
+        self.legal_moves = MoveGen::new_legal(&self.board().board).collect();
     }
 
     pub fn last_move(&self) -> Option<ChessMove> {
         self.last_move
     }
+
+    /// The move the engine made last, if no move was made and no other position was viewed since.
+    pub fn last_engine_move(&self) -> Option<ChessMove> {
+        self.last_engine_move
+    }
+}
+
+/// Removes the move excluded by `GameState::exclude_move_temporary` when dropped.
+struct TemporaryExclusion<'a> {
+    state: &'a mut GameState,
+    /// The move to remove, `None` if it was excluded before.
+    excluded: Option<ChessMove>,
+}
+
+impl Drop for TemporaryExclusion<'_> {
+    fn drop(&mut self) {
+        let exclude_moves = &mut self.state.exclude_moves;
+        if let Some(index) = self
+            .excluded
+            .and_then(|m| exclude_moves.iter().rposition(|other| *other == m))
+        {
+            exclude_moves.remove(index);
+        }
+    }
+}
+
+/// `GameState::delta_move_eval` in the given position.
+fn delta_move_eval(board: &HistoryBoard, m: ChessMove, depth: usize) -> i32 {
+    // without a transposition table, so that the searches don't affect each other
+    let config = ChooserConfig::default();
+    let before = search_eval(board, depth, &config);
+    // the evaluation after the move is from the view of the opponent
+    let after = -search_eval(&board.make_move(m), depth, &config);
+    after - before
+}
+
+/// The evaluation of the given board by a search to the given depth from the view of the side to
+/// move, which is a mate or draw score if the game is over.
+fn search_eval(board: &HistoryBoard, depth: usize, config: &ChooserConfig) -> i32 {
+    let time_control = TimeControl::new(None, TCMode::Depth(depth));
+    match best_move(
+        board,
+        time_control,
+        config,
+        std::io::sink(),
+        std::io::sink(),
+    ) {
+        Some(result) => result.deep_eval,
+        None if board.status() == BoardStatus::Checkmate => -MATE_SCORE,
+        None => 0,
+    }
 }
 
 impl std::default::Default for GameState {
@@ -0,0 +1,329 @@
+use chess::*;
+
+use chessian::historyboard::HistoryBoard;
+use chessian::{ChessianError, move_to_san};
+
+use crate::gamestate::MoveAnnotation;
+
+/// A move of a `GameTree` and the position it leads to.
+#[derive(Clone, Debug)]
+pub struct TreeNode {
+    pub board: HistoryBoard,
+    pub move_played: ChessMove,
+    /// The moves played in `board`, the first one continuing the main line.
+    pub children: Vec<usize>,
+    /// The node of the previous move, `None` for the first moves of the game.
+    pub parent: Option<usize>,
+    pub annotation: Option<MoveAnnotation>,
+}
+
+/// A game with all its variations. Positions are identified by the index of the node of the move
+/// leading to them, `None` being the starting position.
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    root: HistoryBoard,
+    /// The first moves of the game, the first one being the main line.
+    root_children: Vec<usize>,
+    nodes: Vec<TreeNode>,
+}
+
+impl GameTree {
+    pub fn new(root: HistoryBoard) -> Self {
+        Self {
+            root,
+            root_children: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// The position the game started from.
+    pub fn root(&self) -> &HistoryBoard {
+        &self.root
+    }
+
+    pub fn node(&self, node: usize) -> &TreeNode {
+        &self.nodes[node]
+    }
+
+    /// The board of the given position.
+    pub fn board(&self, node: Option<usize>) -> &HistoryBoard {
+        node.map_or(&self.root, |n| &self.nodes[n].board)
+    }
+
+    /// The moves played in the given position.
+    pub fn children(&self, node: Option<usize>) -> &[usize] {
+        node.map_or(&self.root_children, |n| &self.nodes[n].children)
+    }
+
+    /// The other moves played in the position the given move was played in.
+    pub fn alternatives(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.children(self.nodes[node].parent)
+            .iter()
+            .copied()
+            .filter(move |n| *n != node)
+    }
+
+    /// Plays the given move after the given node, returning the node of the move. A move that
+    /// was played there before is not added again.
+    pub fn branch_at(&mut self, node: usize, m: ChessMove) -> usize {
+        self.add_child(Some(node), m)
+    }
+
+    /// Plays the given move in the starting position, returning the node of the move.
+    pub fn branch_at_root(&mut self, m: ChessMove) -> usize {
+        self.add_child(None, m)
+    }
+
+    fn add_child(&mut self, parent: Option<usize>, m: ChessMove) -> usize {
+        if let Some(existing) = self
+            .children(parent)
+            .iter()
+            .copied()
+            .find(|n| self.nodes[*n].move_played == m)
+        {
+            return existing;
+        }
+        let board = self.board(parent).make_move(m);
+        let index = self.nodes.len();
+        self.nodes.push(TreeNode {
+            board,
+            move_played: m,
+            children: Vec::new(),
+            parent,
+            annotation: None,
+        });
+        match parent {
+            Some(p) => self.nodes[p].children.push(index),
+            None => self.root_children.push(index),
+        }
+        index
+    }
+
+    pub fn annotate(&mut self, node: usize, annotation: Option<MoveAnnotation>) {
+        self.nodes[node].annotation = annotation;
+    }
+
+    /// Annotates the given move wherever it was played in the position with the given hash.
+    pub fn annotate_in_position(&mut self, hash: u64, m: ChessMove, annotation: MoveAnnotation) {
+        for node in 0..self.nodes.len() {
+            if self.nodes[node].move_played == m
+                && self.board(self.nodes[node].parent).get_hash() == hash
+            {
+                self.nodes[node].annotation = Some(annotation);
+            }
+        }
+    }
+
+    /// The nodes of the main line of the game, following the first move in each position.
+    pub fn main_line(&self) -> Vec<usize> {
+        self.line_from(None)
+    }
+
+    /// The nodes following the given position on its main line.
+    pub fn line_from(&self, mut node: Option<usize>) -> Vec<usize> {
+        let mut line = Vec::new();
+        while let Some(&next) = self.children(node).first() {
+            line.push(next);
+            node = Some(next);
+        }
+        line
+    }
+
+    /// The nodes of the moves leading to the given node, including itself.
+    pub fn path_to(&self, node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        while let Some(parent) = self.nodes[path[path.len() - 1]].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    /// The game as PGN movetext, with the variations in parentheses. A game that does not start
+    /// from the initial position gets a FEN tag.
+    pub fn to_pgn_with_variations(&self) -> String {
+        let mut pgn = String::new();
+        if self.root.board != Board::default() {
+            pgn.push_str(&format!(
+                "[SetUp \"1\"]\n[FEN \"{}\"]\n\n",
+                self.root.to_fen()
+            ));
+        }
+        let first_ply = (usize::from(self.root.fullmove_number()) - 1) * 2
+            + usize::from(self.root.side_to_move() == Color::Black);
+        self.write_variations(None, first_ply, true, &mut pgn);
+        pgn.push('*');
+        pgn
+    }
+
+    /// Reads a game from PGN, with its variations and move annotations, starting from the
+    /// position of its FEN tag if it has one. Other tags, comments and NAGs are ignored.
+    pub fn from_pgn(pgn: &str) -> Result<Self, ChessianError> {
+        let mut root = HistoryBoard::new(Board::default());
+        let mut movetext = String::new();
+        for line in pgn.lines() {
+            let line = line.trim();
+            if let Some(fen) = line
+                .strip_prefix("[FEN \"")
+                .and_then(|rest| rest.strip_suffix("\"]"))
+            {
+                root = HistoryBoard::from_fen(fen)?;
+            } else if !line.starts_with('[') && !line.starts_with('%') {
+                // rest-of-line comments
+                movetext.push_str(line.split(';').next().unwrap_or_default());
+                movetext.push(' ');
+            }
+        }
+        let mut tree = Self::new(root);
+        // the position before the last move and the last move, for each open variation
+        let mut variations = Vec::new();
+        let mut parent = None;
+        let mut last = None;
+        let mut in_comment = false;
+        let movetext = movetext.replace('(', " ( ").replace(')', " ) ");
+        for token in movetext.split_whitespace() {
+            if in_comment || token.starts_with('{') {
+                in_comment = !token.ends_with('}');
+                continue;
+            }
+            match token {
+                "(" => {
+                    // a variation replaces the last move, so it is played in the same position
+                    if last.is_none() {
+                        return Err(ChessianError::InvalidPgn(String::from(
+                            "variation before the first move",
+                        )));
+                    }
+                    variations.push((parent, last));
+                    last = None;
+                }
+                ")" => {
+                    (parent, last) = variations.pop().ok_or_else(|| {
+                        ChessianError::InvalidPgn(String::from("unmatched parenthesis"))
+                    })?;
+                }
+                "1-0" | "0-1" | "1/2-1/2" | "*" => (),
+                _ if token.starts_with('$') => (),
+                _ => {
+                    // the move number, if any, ends with a dot
+                    let san = token.rsplit('.').next().unwrap_or_default();
+                    // castling written with zeros
+                    let castling;
+                    let san = if san.starts_with("0-0") {
+                        castling = san.replace('0', "O");
+                        castling.as_str()
+                    } else {
+                        san
+                    };
+                    if san.is_empty() {
+                        continue;
+                    }
+                    let symbol_start = san.trim_end_matches(['!', '?']).len();
+                    let (san, symbol) = san.split_at(symbol_start);
+                    if let Some(node) = last {
+                        parent = Some(node);
+                    }
+                    let m = ChessMove::from_san(&tree.board(parent).board, san)
+                        .map_err(|_| ChessianError::InvalidMove(san.to_string()))?;
+                    let node = tree.add_child(parent, m);
+                    tree.annotate(node, MoveAnnotation::from_symbol(symbol));
+                    last = Some(node);
+                }
+            }
+        }
+        if variations.is_empty() {
+            Ok(tree)
+        } else {
+            Err(ChessianError::InvalidPgn(String::from(
+                "unclosed variation",
+            )))
+        }
+    }
+
+    /// Writes the moves played in the given position and their continuations, the main line
+    /// last. `ply` is the number of halfmoves before them, used for the move numbers.
+    fn write_variations(
+        &self,
+        parent: Option<usize>,
+        ply: usize,
+        force_number: bool,
+        pgn: &mut String,
+    ) {
+        let Some((&main, alternatives)) = self.children(parent).split_first() else {
+            return;
+        };
+        self.write_move(main, ply, force_number, pgn);
+        for &alternative in alternatives {
+            pgn.push('(');
+            self.write_move(alternative, ply, true, pgn);
+            self.write_variations(Some(alternative), ply + 1, false, pgn);
+            pgn.truncate(pgn.trim_end().len());
+            pgn.push_str(") ");
+        }
+        // a black move after a variation needs its number again
+        self.write_variations(Some(main), ply + 1, !alternatives.is_empty(), pgn);
+    }
+
+    fn write_move(&self, node: usize, ply: usize, force_number: bool, pgn: &mut String) {
+        let number = ply / 2 + 1;
+        if ply % 2 == 0 {
+            pgn.push_str(&format!("{number}. "));
+        } else if force_number {
+            pgn.push_str(&format!("{number}... "));
+        }
+        let node = &self.nodes[node];
+        let symbol = node.annotation.map_or("", MoveAnnotation::symbol);
+        let san = move_to_san(node.move_played, self.board(node.parent));
+        pgn.push_str(&format!("{san}{symbol} "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(tree: &mut GameTree, parent: Option<usize>, san: &str) -> usize {
+        let m = ChessMove::from_san(&tree.board(parent).board, san).unwrap();
+        tree.add_child(parent, m)
+    }
+
+    #[test]
+    fn pgn_roundtrip_keeps_variations_and_annotations() {
+        let fen = "r3k2r/pppq1ppp/2n2n2/2bpp3/2B1P1b1/2NP1N2/PPPBQPPP/R3K2R w KQkq - 3 8";
+        let mut tree = GameTree::new(HistoryBoard::from_fen(fen).unwrap());
+        let short = play(&mut tree, None, "O-O");
+        let long = play(&mut tree, None, "O-O-O");
+        tree.annotate(long, Some(MoveAnnotation::Dubious));
+        let reply = play(&mut tree, Some(short), "O-O-O");
+        tree.annotate(reply, Some(MoveAnnotation::Good));
+        play(&mut tree, Some(long), "O-O");
+        let pgn = tree.to_pgn_with_variations();
+        let read = GameTree::from_pgn(&pgn).unwrap();
+        assert_eq!(read.to_pgn_with_variations(), pgn);
+        assert_eq!(read.root().to_fen(), fen);
+        assert!(pgn.contains("8. O-O (8. O-O-O?! 8... O-O) 8... O-O-O!"));
+        let main_line = read.main_line();
+        assert_eq!(main_line.len(), 2);
+        let alternative = read.alternatives(main_line[0]).next().unwrap();
+        assert_eq!(
+            read.node(alternative).annotation,
+            Some(MoveAnnotation::Dubious)
+        );
+        assert_eq!(read.children(Some(alternative)).len(), 1);
+        assert_eq!(
+            read.node(main_line[1]).annotation,
+            Some(MoveAnnotation::Good)
+        );
+    }
+
+    #[test]
+    fn from_pgn_reads_numbered_castling_with_zeros() {
+        let tree =
+            GameTree::from_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4.0-0 Nf6 5.d3 0-0 *").unwrap();
+        let main_line = tree.main_line();
+        assert_eq!(main_line.len(), 10);
+        let board = &tree.board(main_line.last().copied()).board;
+        assert_eq!(board.king_square(Color::White), Square::G1);
+        assert_eq!(board.king_square(Color::Black), Square::G8);
+    }
+}
@@ -1,6 +1,9 @@
 use chess::{Color as ChessColor, Piece};
 use macroquad::prelude::*;
 
+/// The built-in piece sprites, 16 pixels in size, in the layout expected by `Textures::load`.
+pub const PIECES_PNG: &[u8] = include_bytes!("../pieces.png");
+
 /// Stores textures for all pieces of all colors for a chess game.
 pub struct Textures {
     white_pawn: Image,
@@ -22,7 +25,19 @@ impl Textures {
     /// the given square size in the order: king, queen, bishop, knight, rook, pawn; black first,
     /// then white below, no paddings.
     pub async fn load(path: &str, sprite_size: f32) -> Self {
-        let pieces = load_image(path).await.unwrap();
+        Self::from_image(&load_image(path).await.unwrap(), sprite_size)
+    }
+
+    /// Loads the textures from the given encoded image, e.g. `PIECES_PNG`, laid out like for
+    /// `Textures::load`.
+    pub fn load_from_bytes(bytes: &[u8], sprite_size: f32) -> Self {
+        Self::from_image(
+            &Image::from_file_with_format(bytes, None).unwrap(),
+            sprite_size,
+        )
+    }
+
+    fn from_image(pieces: &Image, sprite_size: f32) -> Self {
         let piece_order = [5., 3., 2., 4., 1., 0.];
         let mut white_pieces = piece_order.into_iter().map(|x| {
             pieces.sub_image(Rect::new(
@@ -1,28 +1,44 @@
+mod args;
+mod editor;
 mod gamestate;
+mod gametree;
 mod graphics;
+mod ponder;
+mod settings;
 mod utils;
 
-use std::io::Write;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 use std::sync::{
-    Arc,
+    Arc, Mutex,
     atomic::{AtomicBool, Ordering},
     mpsc,
 };
 use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use chess::Color as ChessColor;
 use chess::*;
 use chessian::chooser::*;
+use chessian::eval::{PIECE_VALUES, eval_explain};
+use chessian::tablebase::TablebaseProber;
 use chessian::timecontrol::*;
 use chessian::*;
 use macroquad::color::Color;
 use macroquad::input::KeyCode;
+use macroquad::miniquad::window::{clipboard_get, clipboard_set};
 use macroquad::prelude::*;
 use macroquad::ui::*;
 
-use gamestate::GameState;
-use graphics::Textures;
-use utils::board_to_fen;
+use args::{Args, USAGE};
+use editor::{EN_PASSANT_LABELS, EditorState, en_passant_file};
+use gamestate::{GameResult, GameState, MoveAnnotation};
+use gametree::GameTree;
+use graphics::{PIECES_PNG, Textures};
+use ponder::PonderState;
+use settings::Settings;
+use utils::{LineSender, attack_counts, captured_pieces};
 
 /// Size (in pixels) of the chess squares
 pub const FIELD_SIZE: f32 = 100.0;
@@ -34,72 +50,338 @@ pub const COLOR_BLACK: Color = Color::from_hex(0xFFC0CB);
 pub const COLOR_BLUE: Color = Color::from_hex(0xB3EBF2);
 /// A red color used for accents
 pub const COLOR_RED: Color = Color::from_hex(0xFF746C);
+/// The opacity of the tint of a contested square per attack one side has more on it
+pub const CONTESTED_TINT_STEP: f32 = 0.12;
+/// The highest opacity of the tint of a contested square
+pub const CONTESTED_TINT_MAX: f32 = 0.6;
+/// The color of the arrow of the last move of the computer
+pub const ENGINE_ARROW_COLOR: Color = GREEN;
 /// The radius (in pixels) of the circles indicating legal moves
 pub const MOVE_INDICATOR_SIZE: f32 = 15.0;
 /// The color of the move indicator circle
 pub const MOVE_INDICATOR_COLOR: Color = Color::new(1., 0.1, 0.1, 0.5);
 
+/// The width (in pixels) of the margins left of and below the board holding the coordinates
+pub const COORDINATE_MARGIN: f32 = 20.0;
+/// The color of the coordinates along the board edges
+pub const COORDINATE_COLOR: Color = DARKGRAY;
+
+/// How long (in seconds) to wait between two moves of the computer playing against itself
+pub const SELF_PLAY_DELAY: f64 = 0.5;
+/// How long (in seconds) a status message is shown on screen
+pub const STATUS_MESSAGE_SECONDS: f64 = 2.0;
+
+/// The size (in pixels) of the dialog showing a FEN that failed to load
+pub const FEN_ERROR_DIALOG_SIZE: Vec2 = Vec2::new(400.0, 100.0);
+
 /// The width (in pixels) of the evaluation bar
 pub const EVAL_BAR_W: f32 = 35.0;
 
 /// The width (in pixels) of the side bar gui
 pub const UI_WIDTH: f32 = 200.0;
+/// The height (in pixels) of the move history panel in the side bar
+pub const MOVE_HISTORY_H: f32 = 250.0;
+/// The height (in pixels) of the graph of the background evaluation by depth
+pub const EVAL_GRAPH_H: f32 = 60.0;
+/// The evaluation (in centipawns) at the top and, negated, at the bottom of the graph
+pub const EVAL_GRAPH_RANGE: i32 = 500;
+/// Above this halfmove clock, it is shown in red as the fifty-move rule approaches
+pub const HALFMOVE_CLOCK_WARNING: u8 = 80;
+/// At this halfmove clock, a draw may be claimed by the fifty-move rule
+pub const HALFMOVE_CLOCK_DRAW: u8 = 100;
+/// The space (in pixels) around the pieces in the promotion menu
+pub const PROMOTION_PANEL_PADDING: f32 = 8.0;
+/// The background color of the promotion menu
+pub const PROMOTION_PANEL_COLOR: Color = Color::new(0.2, 0.2, 0.2, 0.9);
+/// The depth from which on background evaluations are used to annotate moves
+pub const ANNOTATION_DEPTH: usize = 5;
+/// The search depth of the analysis annotating all moves of the game
+pub const AUTO_ANNOTATION_DEPTH: usize = 4;
+/// The depth of the search for the best move without the one of the background evaluation
+pub const WHAT_IF_DEPTH: usize = 4;
+/// The depth of the searches of both sides when playing out the game
+pub const PLAY_OUT_DEPTH: usize = 3;
+/// The contempt (in centipawns) of the computer can be set from minus to plus this value
+pub const MAX_CONTEMPT: i32 = 100;
+/// The color of the moves in the move history annotated as mistakes
+pub const COLOR_MISTAKE: Color = ORANGE;
+/// The maximum number of best moves the background evaluation shows
+pub const MAX_BG_EVAL_LINES: usize = 3;
+/// The choices of the side the player plays, the first letting the computer respond as either
+const PLAY_AS_LABELS: [&str; 3] = ["Either side", "White", "Black"];
+/// The choices of the annotation of the last move, the first meaning none
+const ANNOTATION_LABELS: [&str; 7] = ["-", "!!", "!", "!?", "?!", "?", "??"];
+/// The size (in pixels) of the icons of captured pieces in the side bar
+pub const CAPTURED_PIECE_SIZE: f32 = FIELD_SIZE / 5.0;
 const UI_ID_CHECKBOX: Id = 0;
 const UI_ID_CHECKBOX_DSN: Id = 2;
 const UI_ID_CHECKBOX_DP: Id = 3;
 const UI_ID_SLIDER: Id = 4;
-const UI_ID_EVAL: Id = 666;
+const UI_ID_TB_PATH: Id = 5;
+const UI_ID_EDITOR_WK: Id = 6;
+const UI_ID_EDITOR_WQ: Id = 7;
+const UI_ID_EDITOR_BK: Id = 8;
+const UI_ID_EDITOR_BQ: Id = 9;
+const UI_ID_EDITOR_EP: Id = 10;
+const UI_ID_SELF_PLAY: Id = 11;
+const UI_ID_SELF_PLAY_WHITE: Id = 12;
+const UI_ID_SELF_PLAY_BLACK: Id = 13;
+const UI_ID_DIFFICULTY: Id = 14;
+const UI_ID_ANNOTATION: Id = 15;
+const UI_ID_PLAY_AS: Id = 16;
+const UI_ID_CONTEMPT: Id = 17;
+const UI_ID_ENGINE_ARROW: Id = 18;
+const UI_ID_GAME_PATH: Id = 19;
+
+/// What to do with the game file whose path is being entered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GameFileAction {
+    Save,
+    Load,
+}
+
+/// A message of the thread analyzing the game for mistakes and blunders.
+#[derive(Debug)]
+enum AnnotationUpdate {
+    /// The number of moves analyzed so far and the total number.
+    Progress(usize, usize),
+    Done(Vec<(u64, ChessMove, MoveAnnotation)>),
+}
+
+/// A message of the thread playing the game out.
+#[derive(Debug)]
+enum PlayOutUpdate {
+    Move(ChessMove),
+    Done(GameResult),
+}
+
+/// A game being played out by the computer against itself in another thread.
+#[derive(Debug)]
+struct PlayOut {
+    updates: mpsc::Receiver<PlayOutUpdate>,
+    /// Stops the searches of the thread, which ends the game.
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl Drop for PlayOut {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A graph of the evaluations of the background evaluation by depth, which reveals how stable
+/// the evaluation is.
+#[derive(Debug, Default)]
+struct BackgroundEvalPanel {
+    /// The depths and the evaluations (in centipawns, from white's perspective) at them.
+    history: Vec<(usize, i32)>,
+}
+
+impl BackgroundEvalPanel {
+    fn push(&mut self, depth: usize, score: i32) {
+        self.history.push((depth, score));
+    }
+
+    fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    fn draw(&self, ui: &mut Ui) {
+        let mut canvas = ui.canvas();
+        let origin = canvas.cursor();
+        let size = vec2(UI_WIDTH - 20.0, EVAL_GRAPH_H);
+        canvas.request_space(size);
+        canvas.rect(Rect::new(origin.x, origin.y, size.x, size.y), GRAY, None);
+        let zero = origin.y + size.y / 2.0;
+        canvas.line(
+            vec2(origin.x, zero),
+            vec2(origin.x + size.x, zero),
+            LIGHTGRAY,
+        );
+        let step = size.x / (self.history.len().max(2) - 1) as f32;
+        let point = |i: usize, score: i32| {
+            let score = score.clamp(-EVAL_GRAPH_RANGE, EVAL_GRAPH_RANGE) as f32;
+            vec2(
+                origin.x + i as f32 * step,
+                zero - score / EVAL_GRAPH_RANGE as f32 * size.y / 2.0,
+            )
+        };
+        for (i, window) in self.history.windows(2).enumerate() {
+            canvas.line(point(i, window[0].1), point(i + 1, window[1].1), COLOR_RED);
+        }
+    }
+}
 
 /// State of the chess gui.
 #[derive(Debug)]
 struct GuiState {
     /// The alpha rating (in centipawns) of the last move by the computer.
     last_alpha: Option<i32>,
-    /// The depth the computer reached during its last search, sans q-search.
-    last_depth: Option<usize>,
-    /// The amount of milliseconds the computer last searched for in total.
-    last_millis: Option<u128>,
+    /// The result of the last search of the computer for a move.
+    last_result: Option<ChooserResult>,
     /// Automatically move after the play moved?
     auto_respond: bool,
+    /// The side the computer responds as, `None` for either side.
+    computer_color: Option<ChessColor>,
     /// Should the engine make a move next frame?
     engine_move_next_frame: bool,
-    /// Draw square names?
+    /// Should the computer play both sides?
+    self_play: bool,
+    /// How long the computer should search per move when playing itself, for white and black.
+    self_play_millis: [u128; 2],
+    /// The time at which the computer last finished a move.
+    last_engine_move_time: f64,
+    /// The search on the player's time of the position after their predicted move, if any.
+    ponder: Option<PonderState>,
+    /// Draw the coordinates along the board edges?
     draw_square_names: bool,
     /// Draw pieces?
     draw_pieces: bool,
+    /// Show the terms of the static evaluation of the current position?
+    show_eval_breakdown: bool,
+    /// Tint the squares by which side attacks them more often?
+    draw_contested_squares: bool,
+    /// Draw an arrow for the last move of the computer until the next move?
+    show_engine_arrow: bool,
     /// How long the computer should search in total.
     thinking_millis: u128,
+    /// The depth the computer should search to instead of for `thinking_millis`, if any.
+    search_depth: Option<usize>,
+    /// The strength of the moves of the computer, from 1 to `WeaknessConfig::MAX_LEVEL`.
+    difficulty: u8,
     /// Invert the board?
     invert: bool,
     /// Evaluate the position in the background?
     bg_eval: bool,
     /// The current depth of the background evaluation.
     bg_eval_depth: usize,
-    /// The current best move of the background evaluation.
-    bg_eval_best_move: Option<ChessMove>,
+    /// The evaluations of the background evaluation of the current position by depth.
+    bg_eval_panel: BackgroundEvalPanel,
+    /// How many best moves the background evaluation looks for, from 1 to `MAX_BG_EVAL_LINES`.
+    bg_eval_lines: usize,
+    /// The best moves of the background evaluation at the current depth, best first.
+    multipv_results: Vec<ChooserResult>,
+    /// The index of the line in `multipv_results` whose arrow is drawn.
+    selected_line: usize,
     /// The stop flag of the background evaluation.
     bg_eval_stop_flag: Arc<AtomicBool>,
     /// The handle to the background evaluation thread.
-    bg_eval_handle: mpsc::Receiver<Option<ChooserResult>>,
+    bg_eval_handle: mpsc::Receiver<Vec<ChooserResult>>,
+    /// The UCI `info` output of the search for the move of the computer, line by line.
+    thinking_info: mpsc::Receiver<String>,
+    /// The deepest background evaluations (in centipawns, from white's perspective) of the
+    /// positions seen, by hash, to annotate moves with.
+    position_evals: HashMap<u64, i32>,
+    /// The configuration of all searches.
+    chooser_config: ChooserConfig,
+    /// The directory to load Syzygy tablebases from.
+    tablebase_path: String,
+    /// The path of the PGN file to save the game to or load it from.
+    game_path: String,
+    /// Whether the game is being saved or loaded, while its path is entered.
+    game_file_action: Option<GameFileAction>,
+    /// A message to briefly show on screen and the time at which it was issued.
+    status_message: Option<(String, f64)>,
+    /// The updates of the analysis of the game for annotations and its progress so far, while
+    /// it runs.
+    auto_annotation: Option<(mpsc::Receiver<AnnotationUpdate>, String)>,
+    /// The game being played out by the computer, if any.
+    play_out: Option<PlayOut>,
+    /// The FEN that failed to load, shown in a dialog until it is dismissed.
+    fen_error: Option<FenParseError>,
+    /// Is the position editor open?
+    editor_mode: bool,
+    /// The position being set up in the editor.
+    editor: EditorState,
 }
 
-#[macroquad::main(conf)]
-async fn main() -> Result<(), String> {
-    let mut args = std::env::args();
-    let mut game_state = if let Some(fen) = args.nth(1) {
-        GameState::from_fen(&fen)?
-    } else {
-        GameState::default()
+fn main() -> Result<(), String> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    if args.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    // an invalid FEN is shown in a dialog, unless there is no window to show it in
+    let (mut game_state, fen_error) = match args.fen.as_deref().map(GameState::from_fen) {
+        Some(Ok(game_state)) => (game_state, None),
+        Some(Err(e)) if !args.go => (GameState::default(), Some(e)),
+        Some(Err(e)) => return Err(format!("{e}")),
+        None => match &args.pgn {
+            Some(path) => {
+                let pgn =
+                    fs::read_to_string(path).map_err(|e| format!("can't read {path}: {e}"))?;
+                (GameState::from_pgn(&pgn).map_err(|e| format!("{e}"))?, None)
+            }
+            None => (GameState::default(), None),
+        },
     };
+    if fen_error.is_none() {
+        for uci in &args.moves {
+            game_state.make_move_uci(uci).map_err(|e| format!("{e}"))?;
+        }
+    }
+
+    if args.go {
+        let result = best_move(
+            game_state.board(),
+            TimeControl::new(None, time_control_mode(args.time, args.depth)),
+            &ChooserConfig::default(),
+            std::io::stdout(),
+            std::io::sink(),
+        )
+        .ok_or("no legal moves")?;
+        println!("bestmove {} score {}", result.best_move, result.deep_eval);
+        return Ok(());
+    }
+
+    macroquad::Window::from_config(conf(), run(args, game_state, fen_error));
+    Ok(())
+}
 
-    let mut gui_state = GuiState::new(game_state.board());
-    let piece_sprites = Textures::load("pieces.png", 16.0).await;
+/// The mode of the searches of the computer given the optional `--time` and `--depth` arguments.
+fn time_control_mode(millis: Option<u128>, depth: Option<usize>) -> TCMode {
+    match (millis, depth) {
+        (_, Some(depth)) => TCMode::Depth(depth),
+        (Some(millis), None) => TCMode::MoveTime(millis),
+        (None, None) => TCMode::MoveTime(3_000),
+    }
+}
+
+async fn run(args: Args, mut game_state: GameState, fen_error: Option<FenParseError>) {
+    let mut gui_state = GuiState::load_settings(game_state.board());
+    gui_state.fen_error = fen_error;
+    if let Some(millis) = args.time {
+        gui_state.thinking_millis = millis;
+    }
+    gui_state.search_depth = args.depth;
+    if let Some(path) = &args.pgn {
+        gui_state.game_path = path.clone();
+    }
+    if args.black {
+        gui_state.invert = true;
+        gui_state.computer_color = Some(ChessColor::White);
+        gui_state.engine_move_next_frame = game_state.board().side_to_move() == ChessColor::White;
+    }
+    let piece_sprites = if let Some(path) = &args.pieces {
+        Textures::load(path, 16.0).await
+    } else {
+        Textures::load_from_bytes(PIECES_PNG, 16.0)
+    };
     let mut clickable_moves: Vec<ChessMove> = Vec::new();
     let mut pending_promotion_move: Option<ChessMove> = None;
+    // closing the window ends the loop, so that the settings are saved
+    prevent_quit();
 
     loop {
+        if is_quit_requested() {
+            gui_state.save_settings();
+            break;
+        }
         let hovered_square = hovered_square(gui_state.invert);
-        let is_mouse_in_board = mouse_position().0 <= FIELD_SIZE * 8.0;
+        let (mouse_x, mouse_y) = mouse_position();
+        let is_mouse_in_board = (COORDINATE_MARGIN..=COORDINATE_MARGIN + FIELD_SIZE * 8.0)
+            .contains(&mouse_x)
+            && mouse_y <= FIELD_SIZE * 8.0;
 
         draw(
             &mut gui_state,
@@ -109,20 +391,37 @@ async fn main() -> Result<(), String> {
             is_mouse_in_board,
         );
         try_recv_bg_eval(&mut gui_state, &mut game_state);
+        try_recv_ponder(&mut gui_state, &mut game_state);
+        try_recv_auto_annotation(&mut gui_state, &mut game_state);
+        if try_recv_play_out(&mut gui_state, &mut game_state) {
+            clickable_moves.clear();
+        }
 
+        // the promotion menu takes all input until it is closed
         if let Some(pending_promotion) = pending_promotion_move {
-            promotion_menu(
+            if promotion_menu(
                 &mut gui_state,
                 &mut game_state,
                 &piece_sprites,
                 pending_promotion,
-                hovered_square,
-            );
-            pending_promotion_move = None;
+            ) {
+                pending_promotion_move = None;
+            }
+            next_frame().await;
+            continue;
+        }
+
+        if gui_state.self_play && !gui_state.editor_mode && !gui_state.engine_move_next_frame {
+            if let Some(result) = game_result(game_state.board()) {
+                gui_state.self_play = false;
+                gui_state.status_message = Some((result, get_time()));
+            } else if get_time() - gui_state.last_engine_move_time >= SELF_PLAY_DELAY {
+                gui_state.engine_move_next_frame = true;
+            }
         }
 
         if gui_state.engine_move_next_frame {
-            engine_move(&mut gui_state, &mut game_state).await;
+            engine_move(&mut gui_state, &mut game_state, &piece_sprites).await;
             clickable_moves.clear();
             continue;
         }
@@ -130,6 +429,21 @@ async fn main() -> Result<(), String> {
         if let Some(c) = get_char_pressed() {
             handle_char_pressed(&mut gui_state, &mut game_state, c, &mut clickable_moves);
         }
+        if is_control_down() {
+            let navigated = if is_key_pressed(KeyCode::Home) {
+                game_state.undo_all()
+            } else if is_key_pressed(KeyCode::End) {
+                game_state.redo_all()
+            } else {
+                false
+            };
+            if navigated {
+                clickable_moves.clear();
+                if gui_state.bg_eval {
+                    restart_bg_eval(&mut gui_state, &game_state);
+                }
+            }
+        }
 
         if !is_mouse_in_board {
             next_frame().await;
@@ -138,7 +452,9 @@ async fn main() -> Result<(), String> {
 
         draw_clickable_moves(&gui_state, &clickable_moves);
 
-        if is_mouse_button_pressed(MouseButton::Left) {
+        if is_mouse_button_pressed(MouseButton::Left) && gui_state.editor_mode {
+            gui_state.editor.cycle(hovered_square);
+        } else if is_mouse_button_pressed(MouseButton::Left) {
             handle_left_click(
                 &mut gui_state,
                 &mut game_state,
@@ -146,6 +462,8 @@ async fn main() -> Result<(), String> {
                 &mut pending_promotion_move,
                 &mut clickable_moves,
             );
+        } else if is_mouse_button_pressed(MouseButton::Right) {
+            exclude_bg_eval_best_move(&mut gui_state, &mut game_state, hovered_square);
         }
 
         next_frame().await
@@ -159,7 +477,7 @@ fn draw(
     hovered_square: Square,
     is_mouse_in_board: bool,
 ) {
-    draw_ui(gui_state, game_state);
+    draw_ui(gui_state, game_state, piece_sprites);
     draw_eval_bar(gui_state);
     draw_board(
         gui_state,
@@ -169,6 +487,59 @@ fn draw(
         is_mouse_in_board,
     );
     draw_bg_eval_best_move(gui_state);
+    draw_last_engine_move_arrow(gui_state, game_state);
+    draw_status_message(gui_state);
+    draw_fen_error_dialog(gui_state);
+}
+
+/// Shows which field of the FEN that failed to load is malformed.
+fn draw_fen_error_dialog(gui_state: &mut GuiState) {
+    let Some(error) = &gui_state.fen_error else {
+        return;
+    };
+    let mut dismissed = false;
+    let position = Vec2::new(
+        COORDINATE_MARGIN + (FIELD_SIZE * 8.0 - FEN_ERROR_DIALOG_SIZE.x) / 2.0,
+        (FIELD_SIZE * 8.0 - FEN_ERROR_DIALOG_SIZE.y) / 2.0,
+    );
+    root_ui().window(hash!(), position, FEN_ERROR_DIALOG_SIZE, |ui| {
+        ui.label(None, "Can't load the FEN:");
+        ui.label(None, &error.kind.to_string());
+        ui.label(None, &error.input);
+        dismissed = ui.button(None, "OK");
+    });
+    if dismissed {
+        gui_state.fen_error = None;
+    }
+}
+
+fn draw_status_message(gui_state: &mut GuiState) {
+    if gui_state
+        .status_message
+        .as_ref()
+        .is_some_and(|(_, issued)| get_time() - issued > STATUS_MESSAGE_SECONDS)
+    {
+        gui_state.status_message = None;
+    }
+    if let Some((message, _)) = &gui_state.status_message {
+        draw_text(message, COORDINATE_MARGIN + 10.0, 30.0, 30.0, COLOR_RED);
+    }
+}
+
+/// Saves the board as currently rendered as a PNG file in the working directory.
+fn export_screenshot(gui_state: &mut GuiState) {
+    let screen = get_screen_data();
+    let width = COORDINATE_MARGIN + FIELD_SIZE * 8.0;
+    let height = FIELD_SIZE * 8.0 + COORDINATE_MARGIN;
+    // the screen data is stored bottom up
+    let board = screen.sub_image(Rect::new(0.0, screen.height as f32 - height, width, height));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = format!("chessian_{timestamp}.png");
+    board.export_png(&path);
+    gui_state.status_message = Some((format!("Saved {path}"), get_time()));
 }
 
 fn draw_text_centered(text: &str, font_size: f32, color: Color) {
@@ -191,7 +562,7 @@ fn invert_square(sq: Square) -> Square {
 
 fn square_to_xy(square: Square) -> (f32, f32) {
     (
-        square.get_file().to_index() as f32 * FIELD_SIZE,
+        COORDINATE_MARGIN + square.get_file().to_index() as f32 * FIELD_SIZE,
         (7 - square.get_rank().to_index()) as f32 * FIELD_SIZE,
     )
 }
@@ -209,8 +580,8 @@ fn hovered_square(invert: bool) -> Square {
 
 fn square_under(x: f32, y: f32) -> Square {
     Square::make_square(
-        Rank::from_index(7 - (y / FIELD_SIZE) as usize),
-        File::from_index((x / FIELD_SIZE) as usize),
+        Rank::from_index(7 - ((y / FIELD_SIZE) as usize).min(7)),
+        File::from_index((((x - COORDINATE_MARGIN) / FIELD_SIZE) as usize).min(7)),
     )
 }
 
@@ -233,28 +604,32 @@ fn spawn_new_eval_thread(
     board: HistoryBoard,
     stop_flag: &mut Arc<AtomicBool>,
     eval_depth: usize,
-    rec: &mut mpsc::Receiver<Option<ChooserResult>>,
+    lines: usize,
+    config: ChooserConfig,
+    rec: &mut mpsc::Receiver<Vec<ChooserResult>>,
 ) {
     stop_flag.store(true, Ordering::Relaxed);
     // wait for old eval thread to stop
     let _ = rec.recv();
     *stop_flag = Arc::new(AtomicBool::new(false));
-    *rec = spawn_eval_thread(board, eval_depth, stop_flag.clone());
+    *rec = spawn_eval_thread(board, eval_depth, lines, config, stop_flag.clone());
 }
 
 fn spawn_eval_thread(
     board: HistoryBoard,
     depth: usize,
+    lines: usize,
+    config: ChooserConfig,
     stop_flag: Arc<AtomicBool>,
-) -> mpsc::Receiver<Option<ChooserResult>> {
+) -> mpsc::Receiver<Vec<ChooserResult>> {
     let (tx, rx) = mpsc::channel();
 
     thread::spawn(move || {
-        let eval = best_move(
+        let eval = best_move_multipv(
             &board,
+            lines,
             TimeControl::new(Some(stop_flag), TCMode::Depth(depth)),
-            std::io::sink(),
-            std::io::sink(),
+            &config,
         );
         tx.send(eval)
     });
@@ -262,11 +637,95 @@ fn spawn_eval_thread(
     rx
 }
 
-fn draw_ui(gui_state: &mut GuiState, game_state: &mut GameState) {
+/// Draws a row per line of the background evaluation with its move, evaluation (from white's
+/// perspective) and depth. Clicking a row draws the arrow of its move.
+fn draw_multipv_lines(ui: &mut Ui, gui_state: &mut GuiState, game_state: &GameState) {
+    let board = game_state.board();
+    let mut clicked = None;
+    for (i, result) in gui_state.multipv_results.iter().enumerate() {
+        let score = if board.side_to_move() == ChessColor::Black {
+            -result.deep_eval
+        } else {
+            result.deep_eval
+        };
+        let marker = if i == gui_state.selected_line {
+            ">"
+        } else {
+            ""
+        };
+        let label = format!(
+            "{marker}{}. {} {score} (d{})",
+            i + 1,
+            move_to_san(result.best_move, board),
+            result.reached_depth,
+        );
+        if ui.button(None, label.as_str()) {
+            clicked = Some(i);
+        }
+    }
+    if let Some(i) = clicked {
+        gui_state.selected_line = i;
+    }
+}
+
+/// Draws the buttons choosing how many lines the background evaluation looks for, or turning
+/// it off, the current choice in brackets.
+fn draw_bg_eval_lines_selector(ui: &mut Ui, gui_state: &mut GuiState, game_state: &GameState) {
+    ui.label(None, "Lines:");
+    let current = if gui_state.bg_eval {
+        gui_state.bg_eval_lines
+    } else {
+        0
+    };
+    for lines in 0..=MAX_BG_EVAL_LINES {
+        ui.same_line(0.0);
+        let label = if lines == 0 {
+            String::from("Off")
+        } else {
+            lines.to_string()
+        };
+        let label = if lines == current {
+            format!("[{label}]")
+        } else {
+            label
+        };
+        if ui.button(None, label.as_str()) && lines != current {
+            gui_state.bg_eval = lines > 0;
+            if gui_state.bg_eval {
+                gui_state.bg_eval_lines = lines;
+                restart_bg_eval(gui_state, game_state);
+            } else {
+                gui_state.bg_eval_stop_flag.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Draws the halfmove clock, in red once a draw by the fifty-move rule is near.
+fn draw_halfmove_clock(ui: &mut Ui, clock: u8) {
+    let text = if clock >= HALFMOVE_CLOCK_DRAW {
+        format!("HM clock: {clock} (draw claimable)")
+    } else {
+        format!("HM clock: {clock}")
+    };
+    if clock > HALFMOVE_CLOCK_WARNING {
+        let skin = Skin {
+            label_style: ui.style_builder().text_color(RED).build(),
+            ..ui.default_skin()
+        };
+        ui.push_skin(&skin);
+        ui.label(None, &text);
+        ui.pop_skin();
+    } else {
+        ui.label(None, &text);
+    }
+}
+
+fn draw_ui(gui_state: &mut GuiState, game_state: &mut GameState, piece_sprites: &Textures) {
     root_ui().window(
         hash!(),
-        Vec2::new(FIELD_SIZE * 8.0 + EVAL_BAR_W, 0.0),
-        Vec2::new(UI_WIDTH, FIELD_SIZE * 8.0),
+        Vec2::new(COORDINATE_MARGIN + FIELD_SIZE * 8.0 + EVAL_BAR_W, 0.0),
+        Vec2::new(UI_WIDTH, FIELD_SIZE * 8.0 + COORDINATE_MARGIN),
         |ui| {
             ui.separator();
             if let Some(alpha) = gui_state.last_alpha {
@@ -274,46 +733,57 @@ fn draw_ui(gui_state: &mut GuiState, game_state: &mut GameState) {
             } else {
                 ui.label(None, "Eval: None");
             }
+            if let Some(opening) = game_state.opening_name() {
+                ui.label(None, opening);
+            }
             if gui_state.bg_eval {
                 ui.label(None, &format!("Eval depth: {}", gui_state.bg_eval_depth));
+                gui_state.bg_eval_panel.draw(ui);
+                draw_multipv_lines(ui, gui_state, game_state);
             } else {
                 ui.label(None, "No eval");
             }
-            let prev_eval = gui_state.bg_eval;
-            ui.checkbox(UI_ID_EVAL, "Eval", &mut gui_state.bg_eval);
-            if !gui_state.bg_eval {
-                gui_state.bg_eval_stop_flag.store(true, Ordering::Relaxed);
-            } else if !prev_eval {
-                gui_state.bg_eval_depth = 1;
-                spawn_new_eval_thread(
-                    game_state.board().clone(),
-                    &mut gui_state.bg_eval_stop_flag,
-                    gui_state.bg_eval_depth,
-                    &mut gui_state.bg_eval_handle,
-                );
-            }
-            if let Some(depth) = gui_state.last_depth {
-                ui.label(None, &format!("Last depth: {}", depth));
-            } else {
-                ui.label(None, "Last depth: None");
-            }
-            if let Some(millis) = gui_state.last_millis {
-                ui.label(
-                    None,
-                    &format!("Last search: {:.3}s", millis as f64 / 1_000.0),
-                );
+            draw_bg_eval_lines_selector(ui, gui_state, game_state);
+            if let Some(result) = &gui_state.last_result {
+                ui.label(None, "Last search:");
+                // one term per line, as the side bar is too narrow for all of them
+                for term in format!("{result}").split_whitespace() {
+                    ui.label(None, &format!("  {term}"));
+                }
             } else {
                 ui.label(None, "Last search: None");
             }
             ui.separator();
             ui.checkbox(UI_ID_CHECKBOX, "Auto respond", &mut gui_state.auto_respond);
+            draw_play_as(ui, gui_state, game_state);
             ui.checkbox(
                 UI_ID_CHECKBOX_DSN,
-                "Square names",
+                "Coordinates",
                 &mut gui_state.draw_square_names,
             );
             ui.checkbox(UI_ID_CHECKBOX_DP, "Draw pieces", &mut gui_state.draw_pieces);
+            ui.checkbox(
+                UI_ID_ENGINE_ARROW,
+                "Show engine arrow",
+                &mut gui_state.show_engine_arrow,
+            );
             ui.label(None, &format!("Game: {:?}", game_state.board().status()));
+            if !game_state.exclude_moves().is_empty() {
+                let moves: Vec<_> = game_state
+                    .exclude_moves()
+                    .iter()
+                    .map(|m| m.to_string())
+                    .collect();
+                ui.label(None, &format!("Excluded: {}", moves.join(" ")));
+            }
+            draw_halfmove_clock(ui, game_state.halfmove_clock());
+            if let Some(ponder) = &gui_state.ponder {
+                ui.label(None, &format!("Pondering on {}", ponder.predicted_move));
+            }
+            draw_captured_pieces(ui, game_state, piece_sprites);
+            if gui_state.show_eval_breakdown {
+                draw_eval_breakdown(ui, game_state);
+            }
             let mut seconds = gui_state.thinking_millis as f32 / 1000.0;
             ui.slider(UI_ID_SLIDER, "Search time", 0.5..120.0, &mut seconds);
             if ui.button(None, "1s") {
@@ -329,38 +799,358 @@ fn draw_ui(gui_state: &mut GuiState, game_state: &mut GameState) {
                 seconds = 10.0;
             }
             gui_state.thinking_millis = (seconds * 1000.0) as u128;
+            let mut difficulty = gui_state.difficulty as f32;
+            ui.slider(
+                UI_ID_DIFFICULTY,
+                "Difficulty",
+                1.0..WeaknessConfig::MAX_LEVEL as f32,
+                &mut difficulty,
+            );
+            gui_state.difficulty = difficulty.round() as u8;
+            let mut contempt = gui_state.chooser_config.contempt as f32;
+            ui.slider(
+                UI_ID_CONTEMPT,
+                "Contempt",
+                -MAX_CONTEMPT as f32..MAX_CONTEMPT as f32,
+                &mut contempt,
+            );
+            gui_state.chooser_config.contempt = contempt.round() as i32;
             if ui.button(None, "GO, GO, GO!") {
                 gui_state.engine_move_next_frame = true;
             }
+            ui.checkbox(UI_ID_SELF_PLAY, "Play both sides", &mut gui_state.self_play);
+            if gui_state.self_play {
+                for (color, id) in [
+                    (ChessColor::White, UI_ID_SELF_PLAY_WHITE),
+                    (ChessColor::Black, UI_ID_SELF_PLAY_BLACK),
+                ] {
+                    let millis = &mut gui_state.self_play_millis[color.to_index()];
+                    let mut seconds = *millis as f32 / 1000.0;
+                    ui.slider(id, &format!("{color:?} time"), 0.1..30.0, &mut seconds);
+                    *millis = (seconds * 1000.0) as u128;
+                }
+            }
+            if ui.button(None, "|<") && game_state.undo_all() && gui_state.bg_eval {
+                restart_bg_eval(gui_state, game_state);
+            }
+            ui.same_line(25.0);
             if ui.button(None, "< undo") {
                 game_state.undo_move();
                 if gui_state.bg_eval {
-                    gui_state.bg_eval_depth = 1;
-                    spawn_new_eval_thread(
-                        game_state.board().clone(),
-                        &mut gui_state.bg_eval_stop_flag,
-                        gui_state.bg_eval_depth,
-                        &mut gui_state.bg_eval_handle,
-                    );
+                    restart_bg_eval(gui_state, game_state);
                 }
             }
-            ui.same_line(50.0);
+            ui.same_line(80.0);
             if ui.button(None, "redo >") {
                 game_state.redo_move();
                 if gui_state.bg_eval {
-                    gui_state.bg_eval_depth = 1;
-                    spawn_new_eval_thread(
-                        game_state.board().clone(),
-                        &mut gui_state.bg_eval_stop_flag,
-                        gui_state.bg_eval_depth,
-                        &mut gui_state.bg_eval_handle,
-                    );
+                    restart_bg_eval(gui_state, game_state);
+                }
+            }
+            ui.same_line(135.0);
+            if ui.button(None, ">|") && game_state.redo_all() && gui_state.bg_eval {
+                restart_bg_eval(gui_state, game_state);
+            }
+            if let Some(index) = game_state.ply().checked_sub(1) {
+                let current = game_state
+                    .annotation(index)
+                    .and_then(|a| MoveAnnotation::ALL.iter().position(|b| *b == a))
+                    .map_or(0, |i| i + 1);
+                let mut selected = current;
+                ui.combo_box(
+                    UI_ID_ANNOTATION,
+                    "Annotation",
+                    &ANNOTATION_LABELS,
+                    &mut selected,
+                );
+                if selected != current {
+                    match selected.checked_sub(1) {
+                        Some(i) => game_state.annotate_move(index, MoveAnnotation::ALL[i]),
+                        None => game_state.remove_annotation(index),
+                    }
+                }
+            }
+            ui.separator();
+            ui.input_text(UI_ID_TB_PATH, "Tablebases", &mut gui_state.tablebase_path);
+            if ui.button(None, "Load tablebases") {
+                match TablebaseProber::new(&gui_state.tablebase_path) {
+                    Ok(prober) => gui_state.chooser_config.tablebase = Some(Arc::new(prober)),
+                    Err(e) => {
+                        gui_state.status_message =
+                            Some((format!("Failed to load tablebases: {e}"), get_time()))
+                    }
+                }
+            }
+            if gui_state.chooser_config.tablebase.is_some() {
+                ui.label(None, "Tablebases loaded");
+            }
+            ui.separator();
+            draw_game_file_ui(ui, gui_state, game_state);
+            ui.separator();
+            if gui_state.editor_mode {
+                draw_editor_ui(ui, &mut gui_state.editor);
+                if ui.button(None, "Load position") {
+                    toggle_editor(gui_state, game_state);
+                }
+            } else {
+                if let Some(node) = draw_move_history(ui, game_state) {
+                    game_state.go_to_node(node);
+                    if gui_state.bg_eval {
+                        restart_bg_eval(gui_state, game_state);
+                    }
+                }
+                if ui.button(None, "Main line") {
+                    game_state.go_to_main_line();
+                    if gui_state.bg_eval {
+                        restart_bg_eval(gui_state, game_state);
+                    }
                 }
             }
         },
     );
 }
 
+/// Draws the choice of the side the player plays, which makes the computer move right away if it
+/// is to move as the other side.
+fn draw_play_as(ui: &mut Ui, gui_state: &mut GuiState, game_state: &GameState) {
+    let current = match gui_state.computer_color {
+        None => 0,
+        Some(ChessColor::Black) => 1,
+        Some(ChessColor::White) => 2,
+    };
+    let mut selected = current;
+    ui.combo_box(UI_ID_PLAY_AS, "Play as", &PLAY_AS_LABELS, &mut selected);
+    if selected != current {
+        gui_state.computer_color = match selected {
+            1 => Some(ChessColor::Black),
+            2 => Some(ChessColor::White),
+            _otherwise => None,
+        };
+        gui_state.engine_move_next_frame =
+            gui_state.computer_color.is_some() && gui_state.should_respond(game_state.board());
+    }
+}
+
+/// Draws the controls for the side to move, castling rights and en passant file of the position
+/// in the editor.
+fn draw_editor_ui(ui: &mut Ui, editor: &mut EditorState) {
+    ui.label(
+        None,
+        &format!("Editor: {:?} to move", editor.side_to_move()),
+    );
+    if ui.button(None, "Switch side to move") {
+        editor.set_side_to_move(!editor.side_to_move());
+    }
+    for (color, kingside_id, queenside_id) in [
+        (ChessColor::White, UI_ID_EDITOR_WK, UI_ID_EDITOR_WQ),
+        (ChessColor::Black, UI_ID_EDITOR_BK, UI_ID_EDITOR_BQ),
+    ] {
+        let (mut kingside, mut queenside) = editor.castle_rights(color);
+        ui.checkbox(kingside_id, &format!("{color:?} O-O"), &mut kingside);
+        ui.checkbox(queenside_id, &format!("{color:?} O-O-O"), &mut queenside);
+        editor.set_castle_rights(color, kingside, queenside);
+    }
+    let mut en_passant = editor.en_passant().map_or(0, |file| file.to_index() + 1);
+    ui.combo_box(
+        UI_ID_EDITOR_EP,
+        "En passant",
+        &EN_PASSANT_LABELS,
+        &mut en_passant,
+    );
+    editor.set_en_passant(en_passant_file(en_passant));
+}
+
+/// Opens the position editor on the current position, or loads the edited position if it is valid
+/// and closes the editor.
+fn toggle_editor(gui_state: &mut GuiState, game_state: &mut GameState) {
+    if !gui_state.editor_mode {
+        gui_state.editor = EditorState::new(game_state.board());
+        gui_state.editor_mode = true;
+        return;
+    }
+    match gui_state.editor.board() {
+        Ok(board) => {
+            game_state.set_initial_position(HistoryBoard::new(board));
+            gui_state.editor_mode = false;
+            if gui_state.bg_eval {
+                restart_bg_eval(gui_state, game_state);
+            }
+        }
+        Err(e) => gui_state.status_message = Some((format!("Can't load: {e}"), get_time())),
+    }
+}
+
+/// Draws the buttons saving and loading the game and, after one is clicked, the path of the PGN
+/// file to confirm.
+fn draw_game_file_ui(ui: &mut Ui, gui_state: &mut GuiState, game_state: &mut GameState) {
+    if ui.button(None, "Save game") {
+        gui_state.game_file_action = Some(GameFileAction::Save);
+    }
+    ui.same_line(0.0);
+    if ui.button(None, "Load game") {
+        gui_state.game_file_action = Some(GameFileAction::Load);
+    }
+    let Some(action) = gui_state.game_file_action else {
+        return;
+    };
+    ui.input_text(UI_ID_GAME_PATH, "File", &mut gui_state.game_path);
+    let label = match action {
+        GameFileAction::Save => "Save",
+        GameFileAction::Load => "Load",
+    };
+    if ui.button(None, label) {
+        match action {
+            GameFileAction::Save => save_game(gui_state, game_state),
+            GameFileAction::Load => load_game(gui_state, game_state),
+        }
+        gui_state.game_file_action = None;
+    }
+    ui.same_line(0.0);
+    if ui.button(None, "Cancel") {
+        gui_state.game_file_action = None;
+    }
+}
+
+/// The path of the game file in the home directory, `chessian_game.pgn` in the working directory
+/// if there is none.
+fn default_game_path() -> String {
+    dirs::home_dir()
+        .map(|dir| dir.join("chessian_game.pgn"))
+        .unwrap_or_else(|| PathBuf::from("chessian_game.pgn"))
+        .display()
+        .to_string()
+}
+
+/// Writes the game with its variations as PGN to the file at `game_path`.
+fn save_game(gui_state: &mut GuiState, game_state: &GameState) {
+    let path = &gui_state.game_path;
+    let message = match fs::write(path, game_state.to_pgn()) {
+        Ok(()) => format!("Saved {path}"),
+        Err(e) => format!("Can't save: {e}"),
+    };
+    gui_state.status_message = Some((message, get_time()));
+}
+
+/// Replaces the game with the one in the PGN file at `game_path`.
+fn load_game(gui_state: &mut GuiState, game_state: &mut GameState) {
+    let path = &gui_state.game_path;
+    let loaded = fs::read_to_string(path)
+        .map_err(ChessianError::from)
+        .and_then(|pgn| GameState::from_pgn(&pgn));
+    let message = match loaded {
+        Ok(loaded) => {
+            *game_state = loaded;
+            format!("Loaded {path}")
+        }
+        Err(e) => format!("Can't load: {e}"),
+    };
+    gui_state.status_message = Some((message, get_time()));
+    if gui_state.bg_eval {
+        restart_bg_eval(gui_state, game_state);
+    }
+}
+
+/// Draws the terms of the static evaluation of the current position.
+fn draw_eval_breakdown(ui: &mut Ui, game_state: &GameState) {
+    let breakdown = eval_explain(game_state.board());
+    ui.separator();
+    for (name, value) in [
+        ("Material", breakdown.material),
+        ("PST", breakdown.pst),
+        ("Doubled pawns", breakdown.doubled_pawns),
+        ("Passed pawns", breakdown.passed_pawns),
+        ("Isolated pawns", breakdown.isolated_pawns),
+        ("Mobility", breakdown.mobility),
+        ("King safety", breakdown.king_safety),
+        ("Other", breakdown.other),
+        ("Static eval", breakdown.total()),
+    ] {
+        ui.label(None, &format!("{name}: {value}"));
+    }
+}
+
+/// Draws the pieces each side has captured as small icons below the total value of the captures.
+fn draw_captured_pieces(ui: &mut Ui, game_state: &GameState, piece_sprites: &Textures) {
+    let per_row = ((UI_WIDTH - 10.0) / CAPTURED_PIECE_SIZE) as usize;
+    for (captor, color) in [("Black", ChessColor::White), ("White", ChessColor::Black)] {
+        let captured = captured_pieces(game_state.initial_board(), game_state.board(), color);
+        let value: i32 = captured.iter().map(|p| PIECE_VALUES[p.to_index()]).sum();
+        ui.label(
+            None,
+            &format!("{captor} captured: {:.1}", value as f32 / 100.0),
+        );
+        for (i, piece) in captured.into_iter().enumerate() {
+            if i % per_row != 0 {
+                ui.same_line((i % per_row) as f32 * CAPTURED_PIECE_SIZE);
+            }
+            let texture = Texture2D::from_image(piece_sprites.get_piece((piece, color)));
+            texture.set_filter(FilterMode::Nearest);
+            ui.texture(texture, CAPTURED_PIECE_SIZE, CAPTURED_PIECE_SIZE);
+        }
+    }
+}
+
+/// Draws the moves of the line as buttons, mistakes and blunders with a colored badge, each
+/// followed by a collapsible list of the other moves played in its position, and returns the node
+/// of the clicked move, if any.
+fn draw_move_history(ui: &mut Ui, game_state: &GameState) -> Option<usize> {
+    let current = game_state.ply();
+    let tree = game_state.tree();
+    let badge_skin = |ui: &mut Ui, color| Skin {
+        label_style: ui.style_builder().text_color(color).build(),
+        ..ui.default_skin()
+    };
+    let mistake_skin = badge_skin(ui, COLOR_MISTAKE);
+    let blunder_skin = badge_skin(ui, COLOR_RED);
+    let mut clicked = None;
+    widgets::Group::new(hash!(), Vec2::new(UI_WIDTH - 10.0, MOVE_HISTORY_H)).ui(ui, |ui| {
+        for (i, &node) in game_state.line().iter().enumerate() {
+            let marker = if i + 1 == current { ">" } else { "" };
+            if ui.button(
+                None,
+                format!("{marker}{}", move_label(tree, node, i)).as_str(),
+            ) {
+                clicked = Some(node);
+            }
+            let badge = match tree.node(node).annotation {
+                Some(MoveAnnotation::Mistake) => Some((&mistake_skin, "mistake")),
+                Some(MoveAnnotation::Blunder) => Some((&blunder_skin, "blunder")),
+                _otherwise => None,
+            };
+            if let Some((skin, text)) = badge {
+                ui.same_line(0.0);
+                ui.push_skin(skin);
+                ui.label(None, text);
+                ui.pop_skin();
+            }
+            let alternatives: Vec<_> = tree.alternatives(node).collect();
+            if !alternatives.is_empty() {
+                ui.tree_node(hash!("variations", node), "variations", |ui| {
+                    for alternative in alternatives {
+                        if ui.button(None, move_label(tree, alternative, i).as_str()) {
+                            clicked = Some(alternative);
+                        }
+                    }
+                });
+            }
+        }
+    });
+    clicked
+}
+
+/// The numbered move of the given node with its annotation, `ply` being the number of moves
+/// before it.
+fn move_label(tree: &GameTree, node: usize, ply: usize) -> String {
+    let node = tree.node(node);
+    let m = move_to_san(node.move_played, tree.board(node.parent));
+    let symbol = node.annotation.map_or("", MoveAnnotation::symbol);
+    if ply % 2 == 0 {
+        format!("{}. {m}{symbol}", ply / 2 + 1)
+    } else {
+        format!("{}... {m}{symbol}", ply / 2 + 1)
+    }
+}
+
 fn draw_board(
     gui_state: &GuiState,
     game_state: &GameState,
@@ -368,215 +1158,646 @@ fn draw_board(
     hovered_square: Square,
     is_mouse_in_board: bool,
 ) {
+    let attacks = [ChessColor::White, ChessColor::Black]
+        .map(|color| attack_counts(game_state.board(), color));
     for y in 0..=7 {
         for x in 0..=7 {
             let square = Square::make_square(
                 Rank::from_index(if gui_state.invert { y } else { 7 - y }),
                 File::from_index(if gui_state.invert { 7 - x } else { x }),
             );
-            let x_pos = x as f32 * FIELD_SIZE;
+            let x_pos = COORDINATE_MARGIN + x as f32 * FIELD_SIZE;
             let y_pos = y as f32 * FIELD_SIZE;
-            let (color, opp_color) = if (x + y) % 2 == 0 {
-                (COLOR_WHITE, COLOR_BLACK)
+            let color = if (x + y) % 2 == 0 {
+                COLOR_WHITE
             } else {
-                (COLOR_BLACK, COLOR_WHITE)
+                COLOR_BLACK
             };
             // Draw field
             draw_rectangle(x_pos, y_pos, FIELD_SIZE, FIELD_SIZE, color);
+            if gui_state.draw_contested_squares && !gui_state.editor_mode {
+                let i = square.to_index();
+                draw_contested_square(x_pos, y_pos, attacks[0][i], attacks[1][i]);
+            }
             if square == hovered_square && is_mouse_in_board {
                 draw_rectangle_lines(x_pos, y_pos, FIELD_SIZE, FIELD_SIZE, 7.5, COLOR_BLUE);
             }
             // Draw piece?
-            if gui_state.draw_pieces
-                && let Some((piece, color)) = game_state
+            let piece = if gui_state.editor_mode {
+                gui_state.editor.piece_on(square)
+            } else {
+                game_state
                     .board()
                     .piece_on(square)
                     .zip(game_state.board().color_on(square))
+            };
+            if gui_state.draw_pieces
+                && let Some((piece, color)) = piece
             {
                 draw_piece(piece, color, x_pos, y_pos, piece_sprites);
             }
 
-            if gui_state.draw_square_names {
-                draw_text(
-                    &square.to_string(),
-                    x_pos,
-                    y_pos + FIELD_SIZE,
-                    20.0,
-                    opp_color,
-                );
-            }
-
             if let Some(m) = game_state.last_move()
+                && !gui_state.editor_mode
                 && (m.get_source() == square || m.get_dest() == square)
             {
                 draw_rectangle_lines(x_pos, y_pos, FIELD_SIZE, FIELD_SIZE, 7.5, COLOR_RED);
             }
         }
     }
+    draw_rectangle(0.0, 0.0, COORDINATE_MARGIN, FIELD_SIZE * 8.0, COLOR_WHITE);
+    draw_rectangle(
+        0.0,
+        FIELD_SIZE * 8.0,
+        COORDINATE_MARGIN + FIELD_SIZE * 8.0,
+        COORDINATE_MARGIN,
+        COLOR_WHITE,
+    );
+    if gui_state.draw_square_names {
+        draw_coordinates(gui_state.invert);
+    }
+}
+
+/// Tints the square at the given position blue if white attacks it more often and red if black
+/// does, the stronger the bigger the difference.
+fn draw_contested_square(x: f32, y: f32, white_attacks: u8, black_attacks: u8) {
+    let difference = white_attacks as i32 - black_attacks as i32;
+    let alpha = (difference.abs() as f32 * CONTESTED_TINT_STEP).min(CONTESTED_TINT_MAX);
+    let tint = if difference > 0 { BLUE } else { RED };
+    if difference != 0 {
+        draw_rectangle(x, y, FIELD_SIZE, FIELD_SIZE, Color { a: alpha, ..tint });
+    }
+}
+
+/// Draws the ranks along the left and the files along the bottom edge of the board.
+fn draw_coordinates(invert: bool) {
+    for i in 0..=7 {
+        let rank = Rank::from_index(if invert { i } else { 7 - i });
+        let file = File::from_index(if invert { 7 - i } else { i });
+        let offset = i as f32 * FIELD_SIZE + FIELD_SIZE / 2.0;
+        draw_text(
+            &(rank.to_index() + 1).to_string(),
+            COORDINATE_MARGIN / 4.0,
+            offset + 5.0,
+            20.0,
+            COORDINATE_COLOR,
+        );
+        draw_text(
+            &((b'a' + file.to_index() as u8) as char).to_string(),
+            COORDINATE_MARGIN + offset - 5.0,
+            FIELD_SIZE * 8.0 + COORDINATE_MARGIN * 0.75,
+            20.0,
+            COORDINATE_COLOR,
+        );
+    }
 }
 
 fn draw_bg_eval_best_move(gui_state: &GuiState) {
-    if let Some(r) = gui_state.bg_eval_best_move
+    if let Some(r) = gui_state.selected_bg_eval_move()
         && gui_state.bg_eval
     {
-        let (x0, y0) = square_to_xy(if gui_state.invert {
-            invert_square(r.get_source())
-        } else {
-            r.get_source()
-        });
-        let (x1, y1) = square_to_xy(if gui_state.invert {
-            invert_square(r.get_dest())
-        } else {
-            r.get_dest()
-        });
-        draw_line(
-            x0 + FIELD_SIZE / 2.0,
-            y0 + FIELD_SIZE / 2.0,
-            x1 + FIELD_SIZE / 2.0,
-            y1 + FIELD_SIZE / 2.0,
-            5.0,
-            COLOR_RED,
-        );
+        draw_move_arrow(r, gui_state.invert, COLOR_RED);
+    }
+}
+
+/// Draws an arrow for the last move of the computer, until the next move is made.
+fn draw_last_engine_move_arrow(gui_state: &GuiState, game_state: &GameState) {
+    if let Some(m) = game_state.last_engine_move()
+        && gui_state.show_engine_arrow
+        && !gui_state.editor_mode
+    {
+        draw_move_arrow(m, gui_state.invert, ENGINE_ARROW_COLOR);
     }
 }
 
+/// Draws a line from the source to the destination of the given move.
+fn draw_move_arrow(m: ChessMove, invert: bool, color: Color) {
+    let (x0, y0) = square_to_xy(if invert {
+        invert_square(m.get_source())
+    } else {
+        m.get_source()
+    });
+    let (x1, y1) = square_to_xy(if invert {
+        invert_square(m.get_dest())
+    } else {
+        m.get_dest()
+    });
+    draw_line(
+        x0 + FIELD_SIZE / 2.0,
+        y0 + FIELD_SIZE / 2.0,
+        x1 + FIELD_SIZE / 2.0,
+        y1 + FIELD_SIZE / 2.0,
+        5.0,
+        color,
+    );
+}
+
+/// Draws the pieces to promote to in a panel at the destination of the given pawn move and
+/// makes the move once one is clicked. Returns whether the menu is closed, which clicking
+/// outside of it also does.
 fn promotion_menu(
     gui_state: &mut GuiState,
     game_state: &mut GameState,
     piece_sprites: &Textures,
     pawn_move: ChessMove,
-    hovered_square: Square,
-) {
+) -> bool {
+    const PIECES: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
     let dest = pawn_move.get_dest();
-    let to_inner_board = if dest.get_rank() == Rank::First {
-        Square::up
+    let (x, y) = square_to_xy(if gui_state.invert {
+        invert_square(dest)
+    } else {
+        dest
+    });
+    // extend the panel from the destination towards the center of the board and keep it on
+    // the screen
+    let pad = PROMOTION_PANEL_PADDING;
+    let height = PIECES.len() as f32 * FIELD_SIZE;
+    let top = if y < FIELD_SIZE * 4.0 {
+        y
     } else {
-        Square::down
+        y + FIELD_SIZE - height
     };
-    let queen_sq = to_inner_board(&dest).unwrap();
-    let rook_sq = to_inner_board(&queen_sq).unwrap();
-    let bishop_sq = to_inner_board(&rook_sq).unwrap();
-    let knight_sq = to_inner_board(&bishop_sq).unwrap();
-    for (square, piece) in [queen_sq, rook_sq, bishop_sq, knight_sq]
-        .iter()
-        .zip([Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight].iter())
-    {
-        let (x, y) = square_to_xy(if gui_state.invert {
-            invert_square(*square)
-        } else {
-            *square
-        });
-        draw_piece(
-            *piece,
-            game_state.board().side_to_move(),
-            x,
-            y,
-            piece_sprites,
-        );
+    let top = top.clamp(pad, FIELD_SIZE * 8.0 - height);
+    draw_rounded_rectangle(
+        x - pad,
+        top - pad,
+        FIELD_SIZE + 2.0 * pad,
+        height + 2.0 * pad,
+        pad,
+        PROMOTION_PANEL_COLOR,
+    );
+    let color = game_state.board().side_to_move();
+    for (i, piece) in PIECES.into_iter().enumerate() {
+        draw_piece(piece, color, x, top + i as f32 * FIELD_SIZE, piece_sprites);
     }
-    if is_mouse_button_pressed(MouseButton::Left) {
-        let clicked_promotion = if hovered_square == queen_sq {
-            Some(Piece::Queen)
-        } else if hovered_square == rook_sq {
-            Some(Piece::Rook)
-        } else if hovered_square == bishop_sq {
-            Some(Piece::Bishop)
-        } else if hovered_square == knight_sq {
-            Some(Piece::Knight)
-        } else {
-            None
-        };
-        if let Some(promotion) = clicked_promotion {
-            game_state.make_move(ChessMove::new(
-                pawn_move.get_source(),
-                dest,
-                Some(promotion),
-            ));
-            if gui_state.bg_eval {
-                gui_state.bg_eval_depth = 1;
-                spawn_new_eval_thread(
-                    game_state.board().clone(),
-                    &mut gui_state.bg_eval_stop_flag,
-                    gui_state.bg_eval_depth,
-                    &mut gui_state.bg_eval_handle,
-                );
-            }
+    if !is_mouse_button_pressed(MouseButton::Left) {
+        return false;
+    }
+    let (mouse_x, mouse_y) = mouse_position();
+    let clicked_promotion = ((x..x + FIELD_SIZE).contains(&mouse_x)
+        && (top..top + height).contains(&mouse_y))
+    .then(|| PIECES[((mouse_y - top) / FIELD_SIZE) as usize]);
+    if let Some(promotion) = clicked_promotion {
+        game_state.make_move(ChessMove::new(
+            pawn_move.get_source(),
+            dest,
+            Some(promotion),
+        ));
+        if gui_state.bg_eval {
+            restart_bg_eval(gui_state, game_state);
         }
     }
+    true
+}
+
+/// Draws a rectangle with corners rounded by the given radius.
+fn draw_rounded_rectangle(x: f32, y: f32, w: f32, h: f32, radius: f32, color: Color) {
+    draw_rectangle(x + radius, y, w - 2.0 * radius, h, color);
+    draw_rectangle(x, y + radius, w, h - 2.0 * radius, color);
+    for (cx, cy) in [
+        (x + radius, y + radius),
+        (x + w - radius, y + radius),
+        (x + radius, y + h - radius),
+        (x + w - radius, y + h - radius),
+    ] {
+        draw_circle(cx, cy, radius, color);
+    }
 }
 
 fn draw_eval_bar(gui_state: &GuiState) {
+    let bar_x = COORDINATE_MARGIN + FIELD_SIZE * 8.0;
     if let Some(score) = gui_state.last_alpha {
         let pawn_score = score as f32 / 100.0;
         let bar_y = FIELD_SIZE * 4.0 + pawn_score * 25.0;
-        draw_rectangle(FIELD_SIZE * 8.0, bar_y, EVAL_BAR_W, FIELD_SIZE * 8.0, BLACK);
-        draw_rectangle(FIELD_SIZE * 8.0, 0.0, EVAL_BAR_W, bar_y, COLOR_WHITE);
+        draw_rectangle(bar_x, bar_y, EVAL_BAR_W, FIELD_SIZE * 8.0 - bar_y, BLACK);
+        draw_rectangle(bar_x, 0.0, EVAL_BAR_W, bar_y, COLOR_WHITE);
         draw_text(
             &format!("{pawn_score:.1}"),
-            FIELD_SIZE * 8.0,
+            bar_x,
             FIELD_SIZE * 4.0,
             15.0,
             COLOR_RED,
         );
     } else {
-        draw_rectangle(FIELD_SIZE * 8.0, 0.0, EVAL_BAR_W, FIELD_SIZE * 8.0, GRAY);
+        draw_rectangle(bar_x, 0.0, EVAL_BAR_W, FIELD_SIZE * 8.0, GRAY);
     }
 }
 
 fn try_recv_bg_eval(gui_state: &mut GuiState, game_state: &mut GameState) {
-    if let Ok(Some(result)) = gui_state.bg_eval_handle.try_recv() {
+    if let Ok(results) = gui_state.bg_eval_handle.try_recv()
+        && let Some(result) = results.first().cloned()
+    {
         gui_state.last_alpha = Some(if game_state.board().side_to_move() == ChessColor::Black {
             -result.deep_eval
         } else {
             result.deep_eval
         });
-        gui_state.bg_eval_best_move = Some(result.best_move);
+        gui_state.multipv_results = results;
+        if let Some(score) = gui_state.last_alpha {
+            gui_state.bg_eval_panel.push(gui_state.bg_eval_depth, score);
+            // with excluded moves, the evaluation is not the one of the position
+            if gui_state.bg_eval_depth >= ANNOTATION_DEPTH && game_state.exclude_moves().is_empty()
+            {
+                gui_state
+                    .position_evals
+                    .insert(game_state.board().get_hash(), score);
+                annotate_last_move(gui_state, game_state);
+            }
+        }
         if gui_state.bg_eval {
             gui_state.bg_eval_depth += 1;
             spawn_new_eval_thread(
                 game_state.board().clone(),
                 &mut gui_state.bg_eval_stop_flag,
                 gui_state.bg_eval_depth,
+                gui_state.bg_eval_lines,
+                bg_eval_config(gui_state, game_state),
                 &mut gui_state.bg_eval_handle,
             );
         }
     }
 }
 
+/// Starts analyzing the moves of the line for mistakes and blunders in another thread, unless
+/// an analysis is running already.
+fn start_auto_annotation(gui_state: &mut GuiState, game_state: &GameState) {
+    if gui_state.auto_annotation.is_some() {
+        return;
+    }
+    // the analysis works on a copy, so that the game can go on meanwhile
+    let mut analyzed = game_state.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        analyzed.auto_annotate(AUTO_ANNOTATION_DEPTH, |done, total| {
+            let _ = tx.send(AnnotationUpdate::Progress(done, total));
+        });
+        let _ = tx.send(AnnotationUpdate::Done(analyzed.line_annotations()));
+    });
+    gui_state.auto_annotation = Some((rx, String::from("Analyzing the game ...")));
+}
+
+/// Applies the annotations once the analysis of the game is done, showing its progress as the
+/// status message until then.
+fn try_recv_auto_annotation(gui_state: &mut GuiState, game_state: &mut GameState) {
+    let Some((rx, progress)) = &mut gui_state.auto_annotation else {
+        return;
+    };
+    let mut annotations = None;
+    for update in rx.try_iter() {
+        match update {
+            AnnotationUpdate::Progress(done, total) => {
+                *progress = format!("Analyzed {done} of {total} moves");
+            }
+            AnnotationUpdate::Done(done) => annotations = Some(done),
+        }
+    }
+    let message = match annotations {
+        Some(annotations) => {
+            game_state.annotate_moves(&annotations);
+            gui_state.auto_annotation = None;
+            String::from("Annotated the game")
+        }
+        None => progress.clone(),
+    };
+    gui_state.status_message = Some((message, get_time()));
+}
+
+/// Annotates the last move as a mistake or blunder if the background evaluations of the
+/// positions before and after it show that it worsened the position of the side that made it.
+fn annotate_last_move(gui_state: &GuiState, game_state: &mut GameState) {
+    let Some((before, _)) = game_state.history().last() else {
+        return;
+    };
+    let evals = &gui_state.position_evals;
+    let (Some(eval_before), Some(eval_after)) = (
+        evals.get(&before.get_hash()),
+        evals.get(&game_state.board().get_hash()),
+    ) else {
+        return;
+    };
+    let drop = if before.side_to_move() == ChessColor::White {
+        eval_before - eval_after
+    } else {
+        eval_after - eval_before
+    };
+    if let Some(annotation) = MoveAnnotation::from_eval_drop(drop) {
+        game_state.annotate_move(game_state.ply() - 1, annotation);
+    }
+}
+
+/// The configuration of the background evaluation, which skips the excluded moves.
+fn bg_eval_config(gui_state: &GuiState, game_state: &GameState) -> ChooserConfig {
+    ChooserConfig {
+        exclude_moves: game_state.exclude_moves().to_vec(),
+        ..gui_state.chooser_config.clone()
+    }
+}
+
+/// Excludes the selected move of the background evaluation if the given square is the source or
+/// destination of its arrow, so that the evaluation looks for the next best move.
+fn exclude_bg_eval_best_move(gui_state: &mut GuiState, game_state: &mut GameState, square: Square) {
+    if let Some(m) = gui_state.selected_bg_eval_move()
+        && gui_state.bg_eval
+        && (m.get_source() == square || m.get_dest() == square)
+    {
+        game_state.exclude_move(m);
+        restart_bg_eval(gui_state, game_state);
+    }
+}
+
 fn restart_bg_eval(gui_state: &mut GuiState, game_state: &GameState) {
     gui_state.bg_eval_depth = 1;
+    gui_state.bg_eval_panel.clear();
+    gui_state.multipv_results.clear();
+    gui_state.selected_line = 0;
     spawn_new_eval_thread(
         game_state.board().clone(),
         &mut gui_state.bg_eval_stop_flag,
         gui_state.bg_eval_depth,
+        gui_state.bg_eval_lines,
+        bg_eval_config(gui_state, game_state),
         &mut gui_state.bg_eval_handle,
     );
 }
 
-async fn engine_move(gui_state: &mut GuiState, game_state: &mut GameState) {
-    draw_rectangle(
-        0.0,
-        0.0,
-        screen_width(),
-        screen_height(),
-        Color::new(0.0, 0.0, 0.0, 0.75),
-    );
-    draw_text_centered("Engine calculates ...", 35.0, COLOR_BLUE);
-    next_frame().await;
-    if let Some(result) = game_state.engine_move(TimeControl::new(
-        None,
-        TCMode::MoveTime(gui_state.thinking_millis),
-    )) {
-        gui_state.last_alpha = Some(result.deep_eval);
-        gui_state.last_depth = Some(result.reached_depth);
-        gui_state.last_millis = Some(result.millis);
+/// Searches the best move if the selected move of the background evaluation couldn't be played,
+/// showing it as the status message.
+fn what_if_excluded(gui_state: &mut GuiState, game_state: &mut GameState) {
+    let Some(m) = gui_state.selected_bg_eval_move() else {
+        return;
+    };
+    let chooser_config = gui_state.chooser_config.clone();
+    let result = game_state.exclude_move_temporary(m, |state| {
+        let config = ChooserConfig {
+            exclude_moves: state.exclude_moves().to_vec(),
+            ..chooser_config
+        };
+        best_move(
+            state.board(),
+            TimeControl::new(None, TCMode::Depth(WHAT_IF_DEPTH)),
+            &config,
+            std::io::sink(),
+            std::io::sink(),
+        )
+    });
+    let board = game_state.board();
+    let without = move_to_san(m, board);
+    let message = match result {
+        Some(result) => {
+            let score = if board.side_to_move() == ChessColor::Black {
+                -result.deep_eval
+            } else {
+                result.deep_eval
+            };
+            format!(
+                "Without {without}: {} {score}",
+                move_to_san(result.best_move, board)
+            )
+        }
+        None => format!("Without {without}: no other move"),
+    };
+    gui_state.status_message = Some((message, get_time()));
+}
+
+/// Lets the computer play the game out against itself from the current position in another
+/// thread, or stops it if it is doing so already.
+fn toggle_play_out(gui_state: &mut GuiState, game_state: &GameState) {
+    if gui_state.play_out.take().is_some() {
+        gui_state.status_message = Some((String::from("Stopped playing out"), get_time()));
+        return;
     }
+    let mut state = GameState::from_history_board(game_state.board().clone());
+    let chooser = AlphaBetaChooser(engine_config(gui_state));
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let time_control = TimeControl::new(Some(stop_flag.clone()), TCMode::Depth(PLAY_OUT_DEPTH));
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = state.play_game(
+            &chooser,
+            &chooser,
+            &time_control,
+            Some(&|state: &GameState| {
+                if let Some(m) = state.last_move() {
+                    let _ = tx.send(PlayOutUpdate::Move(m));
+                }
+            }),
+        );
+        let _ = tx.send(PlayOutUpdate::Done(result));
+    });
+    gui_state.play_out = Some(PlayOut {
+        updates: rx,
+        stop_flag,
+    });
+}
+
+/// Makes the moves of the game being played out as they arrive, showing its progress as the
+/// status message, and the result once it is over. It is stopped if the position changed
+/// meanwhile so that a move is illegal. Returns whether a move was made.
+fn try_recv_play_out(gui_state: &mut GuiState, game_state: &mut GameState) -> bool {
+    let Some(play_out) = &gui_state.play_out else {
+        return false;
+    };
+    let updates: Vec<_> = play_out.updates.try_iter().collect();
+    let mut moved = false;
+    let mut message = String::from("Playing out the game ...");
+    for update in updates {
+        match update {
+            PlayOutUpdate::Move(m) if game_state.board().legal(m) => {
+                game_state.make_move(m);
+                moved = true;
+            }
+            PlayOutUpdate::Move(_) => {
+                gui_state.play_out = None;
+                message = String::from("Stopped playing out, the position changed");
+                break;
+            }
+            PlayOutUpdate::Done(result) => {
+                gui_state.play_out = None;
+                message = String::from(match result {
+                    GameResult::WhiteWins => "White wins",
+                    GameResult::BlackWins => "Black wins",
+                    GameResult::Draw => "Draw",
+                });
+            }
+        }
+    }
+    gui_state.status_message = Some((message, get_time()));
+    if moved && gui_state.bg_eval {
+        restart_bg_eval(gui_state, game_state);
+    }
+    moved
+}
+
+/// Searches the move of the computer in another thread, showing the progress of the search
+/// meanwhile, and makes it.
+async fn engine_move(
+    gui_state: &mut GuiState,
+    game_state: &mut GameState,
+    piece_sprites: &Textures,
+) {
+    let (info_tx, info_rx) = mpsc::channel();
+    gui_state.thinking_info = info_rx;
+    let (tx, rx) = mpsc::channel();
+    let board = game_state.board().clone();
+    let time_control = TimeControl::new(None, engine_mode(gui_state, game_state));
+    let (depth_tx, depth_rx) = mpsc::channel();
+    let depth_tx = Mutex::new(depth_tx);
+    let config = ChooserConfig {
+        on_depth_complete: Some(SearchDepthCallback(Arc::new(move |_, score, m| {
+            let _ = depth_tx.lock().unwrap().send((score, m));
+        }))),
+        ..engine_config(gui_state)
+    };
+    let side_to_move = board.side_to_move();
+    thread::spawn(move || {
+        let result = best_move(
+            &board,
+            time_control,
+            &config,
+            LineSender::new(info_tx),
+            std::io::sink(),
+        );
+        tx.send(result)
+    });
+    let mut info = String::new();
+    // the best move so far, updated with the evaluation as each depth completes
+    let mut best_so_far = None;
+    let result = loop {
+        if let Ok(result) = rx.try_recv() {
+            break result;
+        }
+        if let Some(line) = gui_state.thinking_info.try_iter().last() {
+            info = line;
+        }
+        if let Some((score, m)) = depth_rx.try_iter().last() {
+            gui_state.last_alpha = Some(if side_to_move == ChessColor::Black {
+                -score
+            } else {
+                score
+            });
+            best_so_far = Some(m);
+        }
+        draw_eval_bar(gui_state);
+        draw_board(gui_state, game_state, piece_sprites, Square::A1, false);
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Color::new(0.0, 0.0, 0.0, 0.75),
+        );
+        if let Some(m) = best_so_far {
+            draw_move_arrow(m, gui_state.invert, COLOR_RED);
+        }
+        draw_text_centered("Engine calculates ...", 35.0, COLOR_BLUE);
+        draw_thinking_info(&info);
+        next_frame().await;
+    };
+    let result = game_state.make_engine_move(result);
     gui_state.engine_move_next_frame = false;
+    finish_engine_move(gui_state, game_state, result);
+}
+
+/// Draws the given `info` line of a search below the center of the screen, one line per key.
+fn draw_thinking_info(info: &str) {
+    const KEYS: [&str; 7] = ["depth", "multipv", "score", "nodes", "nps", "time", "pv"];
+    let mut lines: Vec<String> = Vec::new();
+    for token in info.split_whitespace().skip(1) {
+        match lines.last_mut() {
+            Some(line) if !KEYS.contains(&token) => {
+                line.push(' ');
+                line.push_str(token);
+            }
+            _otherwise => lines.push(token.to_string()),
+        }
+    }
+    let y = screen_height() / 2.0 + 40.0;
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            line,
+            COORDINATE_MARGIN + 20.0,
+            y + i as f32 * 22.0,
+            22.0,
+            COLOR_WHITE,
+        );
+    }
+}
+
+/// The mode of the search for the next move of the computer.
+fn engine_mode(gui_state: &GuiState, game_state: &GameState) -> TCMode {
+    let millis = if gui_state.self_play {
+        gui_state.self_play_millis[game_state.board().side_to_move().to_index()]
+    } else {
+        gui_state.thinking_millis
+    };
+    time_control_mode(Some(millis), gui_state.search_depth)
+}
+
+/// The configuration of the searches for the moves of the computer.
+fn engine_config(gui_state: &GuiState) -> ChooserConfig {
+    // only the moves of the computer are weakened, not the background evaluation
+    ChooserConfig {
+        weakness: WeaknessConfig::from_level(gui_state.difficulty),
+        ..gui_state.chooser_config.clone()
+    }
+}
+
+/// Updates the gui after the computer made the move of the given result and starts pondering on
+/// the predicted response.
+fn finish_engine_move(
+    gui_state: &mut GuiState,
+    game_state: &GameState,
+    result: Option<ChooserResult>,
+) {
+    gui_state.ponder = None;
+    if let Some(result) = result {
+        gui_state.last_alpha = Some(result.deep_eval);
+        if let Some(response) = result.response
+            && gui_state.auto_respond
+            && !gui_state.self_play
+            && game_state.board().legal(response)
+        {
+            gui_state.ponder = Some(PonderState::start(
+                game_state.board(),
+                response,
+                engine_mode(gui_state, game_state),
+                engine_config(gui_state),
+            ));
+        }
+        gui_state.last_result = Some(result);
+    }
+    gui_state.last_engine_move_time = get_time();
     if gui_state.bg_eval {
         restart_bg_eval(gui_state, game_state);
     }
 }
 
+/// Discards the pondering if the game went elsewhere, or makes the move it found after a
+/// ponderhit.
+fn try_recv_ponder(gui_state: &mut GuiState, game_state: &mut GameState) {
+    let Some(ponder) = &gui_state.ponder else {
+        return;
+    };
+    if !ponder.is_current(game_state.board()) {
+        gui_state.ponder = None;
+        return;
+    }
+    let Some(result) = ponder.try_result() else {
+        return;
+    };
+    game_state.make_move(result.best_move);
+    finish_engine_move(gui_state, game_state, Some(result));
+}
+
+/// A description of the result of the game, if it is over.
+fn game_result(board: &HistoryBoard) -> Option<String> {
+    match board.status() {
+        BoardStatus::Ongoing => None,
+        BoardStatus::Checkmate => Some(format!("{:?} wins", !board.side_to_move())),
+        BoardStatus::Stalemate => Some(String::from("Draw")),
+    }
+}
+
 fn draw_clickable_moves(gui_state: &GuiState, clickable_moves: &[ChessMove]) {
     for m in clickable_moves {
         let dest = m.get_dest();
@@ -607,11 +1828,19 @@ fn handle_left_click(
     if side_to_move_clicked {
         *clickable_moves = game_state.legal_moves_from(hovered_square);
     } else {
-        if let Some(m) = clickable_moves
+        let clicked_move = clickable_moves
             .iter()
             .find(|m| m.get_dest() == hovered_square)
-        {
-            let mov = *m;
+            .copied()
+            .or_else(|| {
+                // without a selected piece, clicking a square only one piece can move to moves it
+                let moves = game_state.legal_moves_to(hovered_square);
+                let first = *moves.first()?;
+                (clickable_moves.is_empty()
+                    && moves.iter().all(|m| m.get_source() == first.get_source()))
+                .then_some(first)
+            });
+        if let Some(mov) = clicked_move {
             if mov.get_promotion().is_some() {
                 *pending_promotion_move = Some(mov);
             } else {
@@ -619,27 +1848,58 @@ fn handle_left_click(
                 if gui_state.bg_eval {
                     restart_bg_eval(gui_state, game_state);
                 }
-                gui_state.engine_move_next_frame = gui_state.auto_respond;
+                if let Some(ponder) = &gui_state.ponder
+                    && ponder.predicted_move == mov
+                {
+                    // the computer moves once the pondering search finishes
+                    ponder.ponderhit();
+                } else {
+                    gui_state.ponder = None;
+                    gui_state.engine_move_next_frame = gui_state.should_respond(game_state.board());
+                }
             }
         }
         clickable_moves.clear();
     }
 }
 
+/// Whether the key used for shortcuts, i.e. command on macOS and control elsewhere, is held.
+fn is_control_down() -> bool {
+    if cfg!(target_os = "macos") {
+        is_key_down(KeyCode::LeftSuper) || is_key_down(KeyCode::RightSuper)
+    } else {
+        is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl)
+    }
+}
+
 fn handle_char_pressed(
     gui_state: &mut GuiState,
     game_state: &mut GameState,
     c: char,
     clickable_moves: &mut Vec<ChessMove>,
 ) {
-    let control_down = if cfg!(target_os = "macos") {
-        is_key_down(KeyCode::LeftSuper) || is_key_down(KeyCode::RightSuper)
-    } else {
-        is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl)
-    };
+    let control_down = is_control_down();
     match c {
         'a' => gui_state.auto_respond = !gui_state.auto_respond,
-        'f' => println!("{}", board_to_fen(game_state.board())),
+        'b' => gui_state.self_play = !gui_state.self_play,
+        'f' => println!("{}", game_state.board().to_fen()),
+        'n' => {
+            println!("{}", game_state.legal_moves_san().join(" "));
+            println!("{}", game_state.legal_moves_uci().join(" "));
+        }
+        'g' => println!("{}", game_state.to_pgn()),
+        'c' => clipboard_set(&game_state.board().to_fen()),
+        'v' => match clipboard_get().map(|fen| HistoryBoard::from_fen(fen.trim())) {
+            Some(Ok(board)) => {
+                game_state.set_initial_position(board);
+                clickable_moves.clear();
+                if gui_state.bg_eval {
+                    restart_bg_eval(gui_state, game_state);
+                }
+            }
+            Some(Err(e)) => gui_state.fen_error = Some(e),
+            None => println!("Clipboard is empty"),
+        },
         'm' => {
             gui_state.engine_move_next_frame = true;
             clickable_moves.clear();
@@ -662,45 +1922,127 @@ fn handle_char_pressed(
         }
         's' => gui_state.draw_square_names = !gui_state.draw_square_names,
         'p' => gui_state.draw_pieces = !gui_state.draw_pieces,
+        'l' => gui_state.show_eval_breakdown = !gui_state.show_eval_breakdown,
+        'd' => gui_state.draw_contested_squares = !gui_state.draw_contested_squares,
         'i' => gui_state.invert = !gui_state.invert,
-        'r' => *game_state = GameState::default(),
-        't' => {
-            let history = game_state.history();
-            println!("Analyzing game. Will take {} seconds", history.len() * 3);
-            for (b, _) in history {
-                let result = best_move(
-                    b,
-                    TimeControl::new(None, TCMode::MoveTime(3000)),
-                    std::io::sink(),
-                    std::io::sink(),
-                )
-                .unwrap();
-                print!("{}", result.deep_eval);
-                let _ = std::io::stdout().flush();
+        'x' => export_screenshot(gui_state),
+        'X' => {
+            game_state.clear_exclude_moves();
+            if gui_state.bg_eval {
+                restart_bg_eval(gui_state, game_state);
             }
         }
+        'e' => {
+            toggle_editor(gui_state, game_state);
+            clickable_moves.clear();
+        }
+        'r' => *game_state = GameState::default(),
+        'w' => what_if_excluded(gui_state, game_state),
+        'o' => toggle_play_out(gui_state, game_state),
+        't' => start_auto_annotation(gui_state, game_state),
         _otherwise => (),
     }
 }
 
 impl GuiState {
+    /// Whether the computer should respond in the given position after a move of the player.
+    fn should_respond(&self, board: &Board) -> bool {
+        self.auto_respond
+            && self
+                .computer_color
+                .is_none_or(|color| board.side_to_move() == color)
+    }
+
+    /// The move of the selected line of the background evaluation, or of the best line if there
+    /// are fewer lines now.
+    fn selected_bg_eval_move(&self) -> Option<ChessMove> {
+        self.multipv_results
+            .get(self.selected_line)
+            .or_else(|| self.multipv_results.first())
+            .map(|result| result.best_move)
+    }
+
     fn new(board: &HistoryBoard) -> Self {
         let bg_eval_stop_flag = Arc::new(AtomicBool::new(false));
         Self {
             last_alpha: None,
-            last_depth: None,
-            last_millis: None,
+            last_result: None,
             auto_respond: true,
+            computer_color: None,
             engine_move_next_frame: false,
+            self_play: false,
+            self_play_millis: [1_000, 1_000],
+            last_engine_move_time: 0.0,
+            ponder: None,
             draw_square_names: true,
             draw_pieces: true,
+            show_eval_breakdown: false,
+            draw_contested_squares: false,
+            show_engine_arrow: true,
             thinking_millis: 3_000,
+            search_depth: None,
+            difficulty: WeaknessConfig::MAX_LEVEL,
             invert: false,
             bg_eval: true,
             bg_eval_depth: 1,
-            bg_eval_best_move: None,
+            bg_eval_panel: BackgroundEvalPanel::default(),
+            bg_eval_lines: 1,
+            multipv_results: Vec::new(),
+            selected_line: 0,
             bg_eval_stop_flag: bg_eval_stop_flag.clone(),
-            bg_eval_handle: spawn_eval_thread(board.clone(), 1, bg_eval_stop_flag.clone()),
+            bg_eval_handle: spawn_eval_thread(
+                board.clone(),
+                1,
+                1,
+                ChooserConfig::default(),
+                bg_eval_stop_flag.clone(),
+            ),
+            thinking_info: mpsc::channel().1,
+            position_evals: HashMap::new(),
+            chooser_config: ChooserConfig {
+                tt: Some(Arc::new(TranspositionTable::new(DEFAULT_TT_SIZE_MB))),
+                ..ChooserConfig::default()
+            },
+            tablebase_path: String::new(),
+            game_path: default_game_path(),
+            game_file_action: None,
+            auto_annotation: None,
+            play_out: None,
+            status_message: None,
+            fen_error: None,
+            editor_mode: false,
+            editor: EditorState::new(board),
+        }
+    }
+
+    /// A new state with the preferences from the settings file.
+    fn load_settings(board: &HistoryBoard) -> Self {
+        let settings = Settings::load();
+        let mut gui_state = Self::new(board);
+        gui_state.thinking_millis = settings.thinking_millis.into();
+        gui_state.auto_respond = settings.auto_respond;
+        gui_state.draw_square_names = settings.draw_square_names;
+        gui_state.bg_eval = settings.bg_eval;
+        gui_state.bg_eval_lines = settings.bg_eval_lines.clamp(1, MAX_BG_EVAL_LINES);
+        gui_state.invert = settings.invert;
+        if !gui_state.bg_eval {
+            gui_state.bg_eval_stop_flag.store(true, Ordering::Relaxed);
+        }
+        gui_state
+    }
+
+    /// Writes the preferences to the settings file.
+    fn save_settings(&self) {
+        let settings = Settings {
+            thinking_millis: self.thinking_millis.try_into().unwrap_or(u64::MAX),
+            auto_respond: self.auto_respond,
+            draw_square_names: self.draw_square_names,
+            bg_eval: self.bg_eval,
+            bg_eval_lines: self.bg_eval_lines,
+            invert: self.invert,
+        };
+        if let Err(e) = settings.save() {
+            println!("Failed to save the settings: {e}");
         }
     }
 }
@@ -708,8 +2050,11 @@ impl GuiState {
 fn conf() -> Conf {
     Conf {
         window_title: "Chessian".to_owned(),
-        window_width: 8 * FIELD_SIZE as i32 + EVAL_BAR_W as i32 + UI_WIDTH as i32,
-        window_height: 8 * FIELD_SIZE as i32,
+        window_width: COORDINATE_MARGIN as i32
+            + 8 * FIELD_SIZE as i32
+            + EVAL_BAR_W as i32
+            + UI_WIDTH as i32,
+        window_height: 8 * FIELD_SIZE as i32 + COORDINATE_MARGIN as i32,
         window_resizable: false,
         ..Default::default()
     }
@@ -0,0 +1,100 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+use std::thread;
+
+use chess::ChessMove;
+
+use chessian::HistoryBoard;
+use chessian::chooser::*;
+use chessian::timecontrol::*;
+
+/// A search of the position after the predicted response of the player, running while the
+/// player thinks. Dropping it stops the search.
+#[derive(Debug)]
+pub struct PonderState {
+    /// The move the player is expected to make.
+    pub predicted_move: ChessMove,
+    /// The hash of the position the player is to move in.
+    before: u64,
+    /// The hash of the position after the predicted move, which is searched.
+    after: u64,
+    stop_flag: Arc<AtomicBool>,
+    ponder_flag: Arc<AtomicBool>,
+    result: mpsc::Receiver<Option<ChooserResult>>,
+}
+
+impl PonderState {
+    /// Starts pondering on the given board, on which the player is to move, assuming they play
+    /// the predicted move. After a ponderhit, the search is limited by the given mode, including
+    /// the time spent pondering.
+    pub fn start(
+        board: &HistoryBoard,
+        predicted_move: ChessMove,
+        mode: TCMode,
+        config: ChooserConfig,
+    ) -> Self {
+        let after_move = board.make_move(predicted_move);
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let ponder_flag = Arc::new(AtomicBool::new(true));
+        let time_control =
+            TimeControl::new(Some(stop_flag.clone()), mode).with_ponder_flag(ponder_flag.clone());
+        let (tx, rx) = mpsc::channel();
+        let after = after_move.get_hash();
+        thread::spawn(move || {
+            let result = best_move(
+                &after_move,
+                time_control,
+                &config,
+                std::io::sink(),
+                std::io::sink(),
+            );
+            tx.send(result)
+        });
+        Self {
+            predicted_move,
+            before: board.get_hash(),
+            after,
+            stop_flag,
+            ponder_flag,
+            result: rx,
+        }
+    }
+
+    /// Turns the pondering into a regular search once the player made the predicted move.
+    pub fn ponderhit(&self) {
+        self.ponder_flag.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_hit(&self) -> bool {
+        !self.ponder_flag.load(Ordering::Relaxed)
+    }
+
+    /// Whether the search still concerns the given board, i.e. the board is the one before the
+    /// predicted move or, after a ponderhit, the one after it.
+    pub fn is_current(&self, board: &HistoryBoard) -> bool {
+        let hash = board.get_hash();
+        if self.is_hit() {
+            hash == self.after
+        } else {
+            hash == self.before
+        }
+    }
+
+    /// The result of the search once it finished after a ponderhit.
+    pub fn try_result(&self) -> Option<ChooserResult> {
+        if self.is_hit() {
+            self.result.try_recv().ok().flatten()
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for PonderState {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+    }
+}
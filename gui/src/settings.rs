@@ -0,0 +1,70 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// The preferences of the user that are kept between runs of the GUI, in a TOML file in the
+/// config directory of the platform. Settings missing from the file have their default values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// How long the computer should search in total.
+    pub thinking_millis: u64,
+    /// Automatically move after the play moved?
+    pub auto_respond: bool,
+    /// Draw the coordinates along the board edges?
+    pub draw_square_names: bool,
+    /// Evaluate the position in the background?
+    pub bg_eval: bool,
+    /// How many best moves the background evaluation looks for.
+    pub bg_eval_lines: usize,
+    /// Invert the board?
+    pub invert: bool,
+}
+
+impl Settings {
+    /// The path of the settings file, e.g. `~/.config/chessian/settings.toml` on Linux.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chessian").join("settings.toml"))
+    }
+
+    /// Reads the settings file, falling back to the defaults if there is none or it is
+    /// malformed.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path).map(|text| toml::from_str(&text)) {
+            Ok(Ok(settings)) => settings,
+            Ok(Err(e)) => {
+                println!("Ignoring malformed settings file {}: {e}", path.display());
+                Self::default()
+            }
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Writes the settings file, creating its directory if necessary.
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path().ok_or_else(|| io::Error::other("no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            thinking_millis: 3_000,
+            auto_respond: true,
+            draw_square_names: true,
+            bg_eval: true,
+            bg_eval_lines: 1,
+            invert: false,
+        }
+    }
+}
@@ -1,81 +1,81 @@
-use chess::{ALL_FILES, ALL_RANKS, Board, Color, Piece, Square};
+use std::io::{self, Write};
+use std::sync::mpsc;
 
-pub fn board_to_fen(board: &Board) -> String {
-    let mut fen = String::new();
+use chess::{
+    ALL_PIECES, Board, Color, Piece, get_bishop_moves, get_king_moves, get_knight_moves,
+    get_pawn_attacks, get_rook_moves,
+};
 
-    for rank in ALL_RANKS.into_iter().rev() {
-        let mut empty_count = 0;
-        for file in ALL_FILES {
-            let square = Square::make_square(rank, file);
-            if let Some((piece, color)) = board.piece_on(square).zip(board.color_on(square)) {
-                if empty_count > 0 {
-                    fen.push_str(&empty_count.to_string());
-                    empty_count = 0;
-                }
-                match piece {
-                    Piece::Pawn => fen.push_str(if color == Color::White { "P" } else { "p" }),
-                    Piece::Knight => fen.push_str(if color == Color::White { "N" } else { "n" }),
-                    Piece::Bishop => fen.push_str(if color == Color::White { "B" } else { "b" }),
-                    Piece::Rook => fen.push_str(if color == Color::White { "R" } else { "r" }),
-                    Piece::Queen => fen.push_str(if color == Color::White { "Q" } else { "q" }),
-                    Piece::King => fen.push_str(if color == Color::White { "K" } else { "k" }),
-                }
-            } else {
-                empty_count += 1;
-            }
-        }
+/// Sends everything written to it line by line over a channel, e.g. to show the output of a
+/// search in another thread.
+pub struct LineSender {
+    sender: mpsc::Sender<String>,
+    buffer: Vec<u8>,
+}
 
-        if empty_count > 0 {
-            fen.push_str(&empty_count.to_string());
+impl LineSender {
+    pub fn new(sender: mpsc::Sender<String>) -> Self {
+        Self {
+            sender,
+            buffer: Vec::new(),
         }
+    }
+}
 
-        if rank.to_index() > 0 {
-            fen.push('/');
+impl Write for LineSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            // the receiver may have stopped listening, which doesn't concern the writer
+            let _ = self
+                .sender
+                .send(String::from_utf8_lossy(&line).trim_end().to_string());
         }
+        Ok(buf.len())
     }
 
-    // active color
-    let active_color = if board.side_to_move() == Color::White {
-        "w"
-    } else {
-        "b"
-    };
-    fen.push_str(&format!(" {active_color} "));
-
-    // castle rights
-    let mut any_castle = false;
-    if board.castle_rights(Color::White).has_kingside() {
-        any_castle = true;
-        fen.push('K');
-    }
-    if board.castle_rights(Color::White).has_queenside() {
-        any_castle = true;
-        fen.push('Q');
-    }
-    if board.castle_rights(Color::Black).has_kingside() {
-        any_castle = true;
-        fen.push('k');
-    }
-    if board.castle_rights(Color::Black).has_queenside() {
-        any_castle = true;
-        fen.push('q');
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
+}
 
-    if !any_castle {
-        fen.push('-');
-    }
-    fen.push(' ');
+/// The pieces of the given color that are on `initial` but no longer on `current`, in the
+/// canonical piece order. Promoted pawns count as captured.
+pub fn captured_pieces(initial: &Board, current: &Board, color: Color) -> Vec<Piece> {
+    ALL_PIECES
+        .into_iter()
+        .flat_map(|piece| {
+            let count =
+                |board: &Board| (board.pieces(piece) & board.color_combined(color)).popcnt();
+            std::iter::repeat_n(
+                piece,
+                count(initial).saturating_sub(count(current)) as usize,
+            )
+        })
+        .collect()
+}
 
-    // en passant target square
-    if let Some(en_passant) = board.en_passant() {
-        fen.push_str(&format!("{}", en_passant));
-    } else {
-        fen.push('-');
+/// The number of pieces of the given color attacking each square, indexed by square index.
+/// Sliding pieces attack through no other piece.
+pub fn attack_counts(board: &Board, color: Color) -> [u8; 64] {
+    let mut counts = [0; 64];
+    let occupied = *board.combined();
+    for square in *board.color_combined(color) {
+        let attacks = match board.piece_on(square) {
+            Some(Piece::Pawn) => get_pawn_attacks(square, color, !chess::EMPTY),
+            Some(Piece::Knight) => get_knight_moves(square),
+            Some(Piece::Bishop) => get_bishop_moves(square, occupied),
+            Some(Piece::Rook) => get_rook_moves(square, occupied),
+            Some(Piece::Queen) => {
+                get_bishop_moves(square, occupied) | get_rook_moves(square, occupied)
+            }
+            Some(Piece::King) => get_king_moves(square),
+            None => chess::EMPTY,
+        };
+        for attacked in attacks {
+            counts[attacked.to_index()] += 1;
+        }
     }
-    fen.push(' ');
-
-    // halfmove clock and fullmove number
-    fen.push_str("0 1");
-
-    fen
+    counts
 }
@@ -0,0 +1,57 @@
+use wasm_bindgen::prelude::*;
+
+use chessian::chooser::{ChooserConfig, best_move};
+use chessian::eval::eval;
+use chessian::timecontrol::{TCMode, TimeControl};
+use chessian::{HistoryBoard, move_to_uci, parse_fen};
+
+/// The engine for JavaScript, e.g. to embed it in a web frontend.
+#[wasm_bindgen]
+pub struct WasmEngine {
+    board: HistoryBoard,
+}
+
+#[wasm_bindgen]
+impl WasmEngine {
+    /// Starts from the position of the given FEN.
+    pub fn new_from_fen(fen: &str) -> Result<WasmEngine, JsValue> {
+        let board = parse_fen(fen).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(Self {
+            board: HistoryBoard::new(board),
+        })
+    }
+
+    /// Plays the given move in UCI notation, e.g. `e2e4` or `e7e8q`.
+    pub fn make_move_uci(&mut self, uci: &str) -> Result<(), JsValue> {
+        let m = self
+            .board
+            .parse_uci_move(uci)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.board = self.board.make_move(m);
+        Ok(())
+    }
+
+    /// The best move in UCI notation found by searching to the given depth, empty if there is no
+    /// legal move.
+    pub fn best_move_at_depth(&self, depth: usize) -> String {
+        let time_control = TimeControl::new(None, TCMode::Depth(depth));
+        best_move(
+            &self.board,
+            time_control,
+            &ChooserConfig::default(),
+            std::io::sink(),
+            std::io::sink(),
+        )
+        .map(|result| move_to_uci(result.best_move))
+        .unwrap_or_default()
+    }
+
+    /// The static evaluation of the position in centipawns from white's perspective.
+    pub fn static_eval(&self) -> i32 {
+        eval(&self.board)
+    }
+
+    pub fn fen(&self) -> String {
+        self.board.to_fen()
+    }
+}